@@ -0,0 +1,17 @@
+//! Exports the version of the latest migration as
+//! SCHEMA_VERSION, the way diesel derives it from the
+//! name of the migration directory
+
+use std::fs;
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    let latest = fs::read_dir("migrations").expect("failed to read migrations")
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(String::from))
+        .max()
+        .expect("no migrations found");
+    let version = latest.split('_').next().unwrap_or_default().replace('-', "");
+    println!("cargo:rustc-env=SCHEMA_VERSION={}", version);
+}
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE tags_without_values (
+    id              INTEGER NOT NULL PRIMARY KEY,
+    name            TEXT NOT NULL UNIQUE
+);
+
+INSERT INTO tags_without_values (id, name) SELECT id, name FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_without_values RENAME TO tags;
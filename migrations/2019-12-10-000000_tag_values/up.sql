@@ -0,0 +1,4 @@
+ALTER TABLE tags ADD COLUMN value TEXT;
+
+UPDATE tags SET value = substr(name, instr(name, '=') + 1)
+    WHERE instr(name, '=') > 1 AND instr(name, '=') < length(name);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+-- Dropping and recreating files would cascade into
+-- file_tags, so we drop the columns instead.
+DROP INDEX files_identity;
+ALTER TABLE files DROP COLUMN fingerprint;
+ALTER TABLE files DROP COLUMN inode;
+ALTER TABLE files DROP COLUMN device;
@@ -0,0 +1,5 @@
+ALTER TABLE files ADD COLUMN device INTEGER;
+ALTER TABLE files ADD COLUMN inode INTEGER;
+ALTER TABLE files ADD COLUMN fingerprint TEXT;
+
+CREATE INDEX files_identity ON files (device, inode);
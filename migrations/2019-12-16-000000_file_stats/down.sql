@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE files DROP COLUMN size;
+ALTER TABLE files DROP COLUMN mtime;
+ALTER TABLE files DROP COLUMN ctime;
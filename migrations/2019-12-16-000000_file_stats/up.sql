@@ -0,0 +1,3 @@
+ALTER TABLE files ADD COLUMN ctime INTEGER;
+ALTER TABLE files ADD COLUMN mtime INTEGER;
+ALTER TABLE files ADD COLUMN size INTEGER;
@@ -0,0 +1,4 @@
+CREATE TABLE update_progress (
+    root            TEXT NOT NULL PRIMARY KEY,
+    path            TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE files ADD COLUMN offline BOOLEAN NOT NULL DEFAULT 0;
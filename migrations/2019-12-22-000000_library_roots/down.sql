@@ -0,0 +1,26 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE files_old (
+    id              INTEGER NOT NULL PRIMARY KEY,
+    kind            INTEGER NOT NULL DEFAULT 0,
+    path            TEXT NOT NULL UNIQUE,
+    device          INTEGER,
+    inode           INTEGER,
+    fingerprint     TEXT,
+    ctime           INTEGER,
+    mtime           INTEGER,
+    size            INTEGER,
+    offline         BOOLEAN NOT NULL DEFAULT 0
+);
+
+INSERT INTO files_old (id, kind, path, device, inode, fingerprint, ctime, mtime, size, offline)
+    SELECT id, kind, CASE
+        WHEN root_id IS NULL THEN path
+        WHEN path = '' THEN (SELECT roots.path FROM roots WHERE roots.id = files.root_id)
+        ELSE (SELECT roots.path FROM roots WHERE roots.id = files.root_id) || '/' || path
+    END, device, inode, fingerprint, ctime, mtime, size, offline FROM files;
+
+DROP TABLE files;
+ALTER TABLE files_old RENAME TO files;
+
+CREATE INDEX files_identity ON files (device, inode);
+DROP TABLE roots;
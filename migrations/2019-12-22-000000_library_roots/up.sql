@@ -0,0 +1,29 @@
+CREATE TABLE roots (
+    id              INTEGER NOT NULL PRIMARY KEY,
+    path            TEXT NOT NULL UNIQUE
+);
+
+-- Paths are unique per root now, so the table is
+-- rebuilt without the constraint on path alone.
+CREATE TABLE files_new (
+    id              INTEGER NOT NULL PRIMARY KEY,
+    kind            INTEGER NOT NULL DEFAULT 0,
+    path            TEXT NOT NULL,
+    device          INTEGER,
+    inode           INTEGER,
+    fingerprint     TEXT,
+    ctime           INTEGER,
+    mtime           INTEGER,
+    size            INTEGER,
+    offline         BOOLEAN NOT NULL DEFAULT 0,
+    root_id         INTEGER REFERENCES roots(id)
+);
+
+INSERT INTO files_new (id, kind, path, device, inode, fingerprint, ctime, mtime, size, offline)
+    SELECT id, kind, path, device, inode, fingerprint, ctime, mtime, size, offline FROM files;
+
+DROP TABLE files;
+ALTER TABLE files_new RENAME TO files;
+
+CREATE UNIQUE INDEX files_path ON files (IFNULL(root_id, 0), path);
+CREATE INDEX files_identity ON files (device, inode);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE files DROP COLUMN mode;
+ALTER TABLE files DROP COLUMN gid;
+ALTER TABLE files DROP COLUMN uid;
@@ -0,0 +1,3 @@
+ALTER TABLE files ADD COLUMN uid INTEGER;
+ALTER TABLE files ADD COLUMN gid INTEGER;
+ALTER TABLE files ADD COLUMN mode INTEGER;
@@ -0,0 +1,183 @@
+use super::{import::*, attr, data::update::Walk};
+use std::fs;
+
+/// The version of the manifest format we write
+pub const MANIFEST_VERSION: u32 = 1;
+
+pub mod error {
+    #[derive(Debug, Fail)]
+    pub enum Error {
+        #[fail(display = "unsupported manifest version: {}", version)]
+        UnsupportedVersion { version: u32, },
+    }
+}
+use error::{Error as E};
+
+/// An archived file: its path relative to the archive
+/// root, a digest of its contents and its tags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: String,
+    pub hash: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// A self-describing archive of tags, which survives
+/// tools that drop extended attributes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub root: String,
+    pub files: Vec<Entry>,
+}
+
+impl Manifest {
+
+    /// Create a new, empty manifest
+    pub fn new(root: &Path) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            root: root.to_string_lossy().into(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Archive the tags of a file, skipping untagged files
+    pub fn add(&mut self, file: &attr::File) {
+        let mut tags: Vec<String> = file.iter()
+            .map(|t| t.as_str())
+            .filter(|t| attr::api::ghostbuster(t).is_some())
+            .map(|t| t.into())
+            .collect();
+        if tags.len() == 0 { return }
+        tags.sort();
+        let path = file.path().strip_prefix(&self.root).unwrap_or(file.path());
+        self.files.push(Entry {
+            path: path.to_string_lossy().into(),
+            hash: util::file::fingerprint(file.path_str()),
+            tags: tags,
+        });
+    }
+
+    /// Archive the tags of all files below a directory
+    pub fn add_subtree(&mut self, dir: &str) {
+        let walk = Walk::new(dir.into());
+        for path in walk.files.iter().filter(|p| !attr::store::is_internal(p)) {
+            match attr::File::open(path.clone()) {
+                Ok(file) => self.add(&file),
+                Err(e) => warn!("skipping {}: {}", path.to_string_lossy(), e),
+            }
+        }
+    }
+
+    /// Serialize the manifest as JSON
+    pub fn to_json(&self) -> Res<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Deserialize a manifest from JSON
+    pub fn from_json(json: &str) -> Res<Self> {
+        let manifest: Self = serde_json::from_str(json)?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(E::UnsupportedVersion { version: manifest.version }.into())
+        }
+        Ok(manifest)
+    }
+
+    /// Read a manifest from a file
+    pub fn read(path: &Path) -> Res<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+/// Where an archived file has been found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Located {
+    Path(PathBuf),
+    Hash(PathBuf),
+    Missing,
+}
+
+/// Restores archived tags below a root directory.
+/// Files are matched by path first, and by the hash
+/// of their contents once they have moved.
+pub struct Restore {
+    root: PathBuf,
+    hashes: Option<HashMap<String, PathBuf>>,
+}
+
+impl Restore {
+
+    /// Create a new restore below the given root
+    pub fn new(root: &Path) -> Self {
+        Self { root: root.to_owned(), hashes: None }
+    }
+
+    /// Hash all files below root, once
+    fn hashes(&mut self) -> &HashMap<String, PathBuf> {
+        let root = &self.root;
+        self.hashes.get_or_insert_with(|| {
+            profile!("hash", {
+                let walk = Walk::new(root.to_string_lossy().into());
+                walk.files.into_iter()
+                    .filter(|p| !attr::store::is_internal(p))
+                    .filter_map(|p| {
+                        let hash = p.to_str().and_then(util::file::fingerprint)?;
+                        Some((hash, p))
+                    })
+                    .collect()
+            })
+        })
+    }
+
+    /// Locate the file an entry refers to
+    pub fn locate(&mut self, entry: &Entry) -> Located {
+        let path = self.root.join(&entry.path);
+        let matches = |p: &Path| match entry.hash {
+            Some(ref hash) => p.to_str().and_then(util::file::fingerprint).as_ref() == Some(hash),
+            None => p.exists(),
+        };
+        if matches(&path) {
+            return Located::Path(path)
+        }
+        match entry.hash {
+            Some(ref hash) => match self.hashes().get(hash) {
+                Some(p) => Located::Hash(p.clone()),
+                None => Located::Missing,
+            },
+            None => Located::Missing,
+        }
+    }
+
+    /// Re-apply the tags of an entry to a file. Returns
+    /// a bool indicating changed tags
+    pub fn apply(path: &Path, entry: &Entry, commit: bool) -> Res<bool> {
+        let mut file = attr::File::open(path.to_owned())?;
+        let mut changed = false;
+        for tag in entry.tags.iter() {
+            if !file.has(tag) { changed |= file.add(tag)?; }
+        }
+        if commit { file.save()?; }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+    use crate::util::testing;
+
+    #[test]
+    fn check_apply() {
+        let (_tmp, dir) = testing::temp_dir();
+        let path = dir.join("a");
+        fs::write(&path, "a").unwrap();
+        let entry = Entry { path: "a".into(), hash: None, tags: vec!["foo".into(), "bar".into()] };
+        assert_eq!(Restore::apply(&path, &entry, false).unwrap(), true);
+        assert_eq!(attr::File::open(path.clone()).unwrap().has("foo"), false);
+        assert_eq!(Restore::apply(&path, &entry, true).unwrap(), true);
+        assert_eq!(Restore::apply(&path, &entry, true).unwrap(), false);
+        let partial = Entry { tags: vec!["foo".into(), "baz".into(), "bar".into()], ..entry };
+        assert_eq!(Restore::apply(&path, &partial, true).unwrap(), true);
+    }
+}
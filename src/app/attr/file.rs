@@ -1,13 +1,16 @@
-use super::{import::*, api, Tag, error::{Error as E}};
+use super::{import::*, api, store, Tag, TagStore, error::{Error as E}};
 use crate::model::{file, tag};
+use std::sync::Arc;
 
 /// A file and it's associated tag data. Provides
 /// methods for opening, querying, modifying and saving
-/// tags using extended filesystem attributes.
+/// tags using the TagStore responsible for the file,
+/// extended filesystem attributes by default.
 #[derive(Clone)]
 pub struct File {
     path: PathBuf,
     tags: HashSet<Tag>,
+    store: Arc<dyn TagStore>,
     dirty: bool,
 }
 
@@ -16,9 +19,29 @@ impl File {
     /// Opens a new file and reads the filesystems
     /// tag data
     pub fn open(path: PathBuf) -> Res<Self> {
-        let tags = api::read(&path)?;
+        let store = store::select(&path);
+        Self::open_with(path, store)
+    }
+
+    /// Opens a new file and reads the tag data
+    /// from the given store. Paths must be valid UTF-8.
+    pub fn open_with(path: PathBuf, store: Arc<dyn TagStore>) -> Res<Self> {
+        if path.to_str().is_none() {
+            return Err(E::UnsupportedPath { path: path.to_string_lossy().into() }.into())
+        }
+        let raw = store.read(&path)?;
+        Self::from_raw(path, store, raw)
+    }
+
+    /// Decodes tag data previously read from the given
+    /// store, so reading can happen on another thread
+    pub fn from_raw(path: PathBuf, store: Arc<dyn TagStore>, raw: Option<Vec<u8>>) -> Res<Self> {
+        let tags = match raw {
+            Some(raw) => api::decode(raw, TAG_SEPERATOR)?,
+            None => api::initial_tags(),
+        };
         assert!(tags.len() > 0, "a file should never have an empty tag-set: {}", path.to_string_lossy());
-        Ok(File { path: path, tags: tags, dirty: false })
+        Ok(File { path: path, tags: tags, store: store, dirty: false })
     }
 
     /// Return this files path
@@ -74,7 +97,7 @@ impl File {
     pub fn set_tags(&mut self, tags: &HashSet<Tag>) -> bool {
         self.dirty = self.tags.len() > 0;
         self.tags.clear();
-        self.tags.extend(tags.iter().map(|e| e.clone()));
+        self.tags.extend(tags.iter().cloned());
         self.dirty
     }
 
@@ -139,7 +162,7 @@ impl File {
     /// Add a tag to the file
     pub fn add(&mut self, tag: &str) -> Res<bool> {
         // NOTE: potentially save an allocation here
-        if self.tags.insert(Tag::new(tag.into())?) {
+        if self.tags.insert(Tag::new(tag)?) {
             self.dirty = true;
         }
         Ok(self.dirty)
@@ -166,7 +189,7 @@ impl File {
 
     /// Write tags back to the filesystem unconditionally
     pub fn force_save(&mut self) -> Res<()> {
-        api::write(&self.path, &self.tags)?;
+        api::write_to(&*self.store, &self.path, &self.tags)?;
         self.dirty = false;
         Ok(())
     }
@@ -191,7 +214,7 @@ mod suite {
             Ok(file) => file,
             Err(err) => {
                 let cwd = env::current_dir().unwrap();
-                panic!(format!("unable to open {}/{}: {:?}", cwd.to_str().unwrap(), path, err));
+                panic!("unable to open {}/{}: {:?}", cwd.to_str().unwrap(), path, err);
             }
         }
     }
@@ -233,6 +256,51 @@ mod suite {
         }
     }
 
+    #[test]
+    fn check_save_chunked() {
+        use super::super::import::*;
+        let path = defaults::test_path("files", "c");
+        let tags: Vec<String> = (0..128).map(|n| format!("Chunked::Tag::{:04}", n)).collect();
+        {
+            let mut file = open_file(&path);
+            for tag in tags.iter() { file.add(tag).unwrap(); }
+            assert_eq!(file.save().unwrap(), true);
+        }
+        {
+            assert!(xattr::get(&path, TAG_KEY).unwrap().is_none());
+            assert!(xattr::get(&path, store::chunk_key(0)).unwrap().is_some());
+            let mut file = open_file(&path);
+            for tag in tags.iter() { assert_eq!(file.has(tag), true); }
+            file.purge();
+            file.force_save().unwrap();
+        }
+        assert!(xattr::get(&path, store::chunk_key(0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn check_save_escaped() {
+        let path = defaults::test_path("files", "d");
+        let tags = vec!["Crosby, Stills & Nash", "C:\\Windows\\,", ",,"];
+        {
+            let mut file = open_file(&path);
+            for tag in tags.iter() { file.add(tag).unwrap(); }
+            assert_eq!(file.save().unwrap(), true);
+        }
+        {
+            let mut file = open_file(&path);
+            for tag in tags.iter() { assert_eq!(file.del(tag), true); }
+            assert_eq!(file.save().unwrap(), true);
+        }
+    }
+
+    #[test]
+    fn check_legacy_decode() {
+        let tags = api::decode(b"foo,bar=1".to_vec(), TAG_SEPERATOR).unwrap();
+        assert!(tags.contains("foo"));
+        assert!(tags.contains("bar=1"));
+        assert!(api::is_legacy(b"foo,bar=1"));
+    }
+
     #[test]
     fn check_api_tag() {
         use super::super::import::*;
@@ -1,13 +1,29 @@
 mod file;
 mod tag;
+pub mod store;
 
 pub mod prelude {
     /// The key under which we store our attribute data
     pub const TAG_KEY: &'static str = "user.tag.list";
 
+    /// The maximum size of a single attribute value. Larger
+    /// tag data is spread over numbered chunks: <TAG_KEY>.<n>
+    pub const TAG_CHUNK_SIZE: usize = 2048;
+
     /// The tag seperator for serialization
     pub const TAG_SEPERATOR: &'static str = ",";
 
+    /// Escapes seperators and itself within encoded tags
+    pub const TAG_ESCAPE: char = '\\';
+
+    /// The header byte of the current, escaped encoding.
+    /// Data without a header uses the legacy encoding,
+    /// which simply joins tags with a seperator.
+    pub const TAG_FORMAT_V2: u8 = 0x02;
+
+    /// Seperates the key from the value in key=value tags
+    pub const VALUE_SEPERATOR: &'static str = "=";
+
     /// The tag seperator for serialization
     pub const API_TAG: &'static str = "tdb::api::Entity";
 }
@@ -20,6 +36,7 @@ pub mod import {
 pub mod export {
     pub use super::file::*;
     pub use super::tag::*;
+    pub use super::store::{TagStore, Stores};
     pub use super::prelude::*;
 }
 pub use export::*;
@@ -31,11 +48,13 @@ pub mod error {
         InvalidTag { tag: String, },
         #[fail(display = "not unlinkable: {}", path)]
         NotUnlinkable { path: String, },
+        #[fail(display = "unsupported path: {}", path)]
+        UnsupportedPath { path: String, },
     }
 }
 
 pub mod api {
-    use super::{import::*, export::*, error::{Error as E}};
+    use super::{import::*, export::*, store::{self, TagStore}, error::{Error as E}};
     use regex::Regex;
 
     const DELIMITER: (&'static str, &'static str) = ("{{", "}}");
@@ -46,20 +65,34 @@ pub mod api {
                 regex::escape(DELIMITER.0),
                 regex::escape(DELIMITER.1),
             );
-            Regex::new(&format!(r".*({}|{}|,).*", open, close))
+            Regex::new(&format!(r".*({}|{}).*", open, close))
                 .expect("failed to compile regex")
         };
     }
 
-    /// Tag sanitization
+    /// Tag sanitization. Key=value tags need both a key
+    /// and a value.
     pub fn sanitize<'a>(tag: &'a str) -> Res<&'a str> {
-        if tag.len() == 0 || INVALID.is_match(tag) {
+        let invalid = match split_value(tag) {
+            (key, Some(value)) => key.len() == 0 || value.len() == 0,
+            (_, None) => false,
+        };
+        if tag.len() == 0 || invalid || INVALID.is_match(tag) {
             Err(E::InvalidTag { tag: tag.into() }.into())
         } else {
             Ok(tag)
         }
     }
 
+    /// Split a tag into key and value at the first
+    /// VALUE_SEPERATOR. Plain tags have no value.
+    pub fn split_value<'a>(tag: &'a str) -> (&'a str, Option<&'a str>) {
+        match tag.find(VALUE_SEPERATOR) {
+            Some(i) => (&tag[..i], Some(&tag[i + VALUE_SEPERATOR.len()..])),
+            None => (tag, None),
+        }
+    }
+
     pub fn ghostbuster(tag: &str) -> Option<&str> {
         if !tag.starts_with("tdb::") {
             Some(tag)
@@ -69,49 +102,63 @@ pub mod api {
     }
 
     /// Generate an initial tag-set
-    fn initial_tags() -> HashSet<Tag> {
+    pub fn initial_tags() -> HashSet<Tag> {
         let mut set = HashSet::new();
         set.insert(Tag::new(API_TAG).expect("misconfigured api tag"));
         set
     }
 
-    /// Read tag data from file
-    pub fn read_with_sep(path: &Path, sep: &str) -> Res<HashSet<Tag>> {
-        match xattr::get(path, TAG_KEY)? {
+    /// Read tag data from file using the given store.
+    /// The seperator is only used for legacy encoded data.
+    pub fn read_from(store: &dyn TagStore, path: &Path, sep: &str) -> Res<HashSet<Tag>> {
+        match store.read(path)? {
             Some(tags) => { decode(tags, sep) },
             None => { Ok(initial_tags()) }
         }
     }
 
+    /// Read tag data from file
+    pub fn read_with_sep(path: &Path, sep: &str) -> Res<HashSet<Tag>> {
+        read_from(&*store::select(path), path, sep)
+    }
+
     /// Read tag data from file
     pub fn read(path: &Path) -> Res<HashSet<Tag>> {
         read_with_sep(path, TAG_SEPERATOR)
     }
 
-    /// Write tag data to file
-    pub fn write_with_sep(path: &Path, tags: &HashSet<Tag>, sep: &str) -> Res<()> {
-        match encode(tags, sep) {
-            Some(encoded) => { xattr::set(path, TAG_KEY, &encoded)?; },
-            None => { purge(path)?; },
+    /// Write tag data to file using the given store
+    pub fn write_to(store: &dyn TagStore, path: &Path, tags: &HashSet<Tag>) -> Res<()> {
+        match encode(tags) {
+            Some(encoded) => { store.write(path, &encoded)?; },
+            None => { store.purge(path)?; },
         }
         Ok(())
     }
 
     /// Write tag data to file
     pub fn write(path: &Path, tags: &HashSet<Tag>) -> Res<()> {
-        write_with_sep(path, tags, TAG_SEPERATOR)
+        write_to(&*store::select(path), path, tags)
     }
 
     /// Purge any tag data from the file
     pub fn purge(path: &Path) -> Res<()> {
-        Ok(xattr::remove(path, TAG_KEY)?)
+        store::select(path).purge(path)
     }
 
-    /// Format the given tags to a string
-    pub fn migrate_seperator(path: &Path, src: &str, dst: &str) -> Res<()> {
-        let tags = read_with_sep(path, src)?;
-        write_with_sep(path, &tags, dst)?;
-        Ok(())
+    /// Rewrite legacy encoded tag data, joined by the
+    /// given seperator, in the current encoding.
+    /// Returns a bool indicating if data has been written
+    pub fn migrate(path: &Path, sep: &str) -> Res<bool> {
+        let store = store::select(path);
+        match store.read(path)? {
+            Some(ref raw) if !is_legacy(raw) => Ok(false),
+            Some(raw) => {
+                write_to(&*store, path, &decode(raw, sep)?)?;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
     }
 
     /// Format the given tags to a string
@@ -131,19 +178,79 @@ pub mod api {
         }
     }
 
-    /// Decode and sanitize the given tags as UTF-8
+    /// Indicates if raw tag data lacks a format header
+    pub fn is_legacy(raw: &[u8]) -> bool {
+        raw.first() != Some(&TAG_FORMAT_V2)
+    }
+
+    /// Split a string at unescaped seperators, removing
+    /// the escape characters
+    fn split_escaped(s: &str) -> Res<Vec<String>> {
+        let mut entries = Vec::new();
+        let mut entry = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == TAG_ESCAPE {
+                match chars.next() {
+                    Some(e) => entry.push(e),
+                    None => return Err(E::InvalidTag { tag: entry }.into()),
+                }
+            } else if TAG_SEPERATOR.starts_with(c) {
+                entries.push(std::mem::take(&mut entry));
+            } else {
+                entry.push(c);
+            }
+        }
+        if s.len() > 0 { entries.push(entry); }
+        Ok(entries)
+    }
+
+    /// Escape seperators and escape characters in a tag
+    fn escape(tag: &str) -> String {
+        let mut escaped = String::with_capacity(tag.len());
+        for c in tag.chars() {
+            if c == TAG_ESCAPE || TAG_SEPERATOR.starts_with(c) {
+                escaped.push(TAG_ESCAPE);
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    /// Decode and sanitize the given tags as UTF-8. The
+    /// encoding is detected by the header byte, legacy
+    /// data is split at the given seperator.
     pub fn decode(tags: Vec<u8>, sep: &str) -> Res<HashSet<Tag>> {
-        String::from_utf8(tags)?
-            .split(sep)
+        let entries: Vec<String> = if is_legacy(&tags) {
+            String::from_utf8(tags)?.split(sep).map(|t| t.into()).collect()
+        } else {
+            split_escaped(&String::from_utf8(tags[1..].to_vec())?)?
+        };
+        entries.iter()
             .try_fold(initial_tags(), |mut set, tag| {
-                let t = Tag::new(tag.into())?;
+                let t = Tag::new(tag)?;
                 if !set.insert(t) { warn!("duplicate tag: '{}'", tag); }
                 Ok(set)
             })
     }
 
-    /// Encode the given tags as UTF-8
-    pub fn encode(tags: &HashSet<Tag>, sep: &str) -> Option<Vec<u8>> {
-        format(tags, sep).map(|e| e.as_bytes().to_vec())
+    /// Encode the given tags as UTF-8, prefixed by the
+    /// format header. Seperators within tags are escaped.
+    pub fn encode(tags: &HashSet<Tag>) -> Option<Vec<u8>> {
+        let mut list: Vec<String> =
+            tags.iter()
+                .map(|tag| tag.as_str())
+                .filter(|tag| ghostbuster(tag).is_some())
+                .filter(|tag| sanitize(tag).is_ok())
+                .map(escape)
+                .collect();
+        if list.len() == 0 {
+            None
+        } else {
+            list.sort();
+            let mut encoded = vec![TAG_FORMAT_V2];
+            encoded.extend(list.join(TAG_SEPERATOR).into_bytes());
+            Some(encoded)
+        }
     }
 }
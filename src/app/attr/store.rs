@@ -0,0 +1,410 @@
+use super::{import::*, error::{Error as E}};
+use std::{ffi::OsString, fs, io::ErrorKind, sync::{Arc, RwLock}};
+
+/// The file name suffix of sidecar files
+pub const SIDECAR_SUFFIX: &'static str = ".tags";
+
+/// The file name of per-directory sidecar files
+pub const DIRECTORY_SIDECAR: &'static str = ".tags";
+
+/// The file name suffix of files which are written and
+/// then renamed into place
+pub const TEMP_SUFFIX: &'static str = ".tmp";
+
+/// A backend which persists the raw, encoded tag data
+/// of files. Encoding and decoding is left to the api,
+/// so every store supports the same format.
+pub trait TagStore: Send + Sync {
+    /// Read the raw tag data of a file
+    fn read(&self, path: &Path) -> Res<Option<Vec<u8>>>;
+    /// Write the raw tag data of a file
+    fn write(&self, path: &Path, raw: &[u8]) -> Res<()>;
+    /// Remove any tag data of a file
+    fn purge(&self, path: &Path) -> Res<()>;
+    /// Indicates if the path is used internally by this
+    /// store, in which case we don't tag or index it
+    fn is_internal(&self, _path: &Path) -> bool { false }
+    /// Indicates if writing tags changes the ctime of the
+    /// file, so unchanged files can be skipped on update
+    fn changes_ctime(&self) -> bool { false }
+}
+
+/// The kinds of stores we support
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[derive(Default)]
+pub enum Kind {
+    /// Extended filesystem attributes
+    #[default]
+    Xattr,
+    /// A hidden '.<name>.tags' file next to each file
+    Sidecar,
+    /// A single hidden '.tags' file per directory
+    Directory,
+}
+
+impl Kind {
+    /// Create a new store of this kind
+    pub fn store(self) -> Arc<dyn TagStore> {
+        match self {
+            Kind::Xattr => Arc::new(Xattr),
+            Kind::Sidecar => Arc::new(Sidecar),
+            Kind::Directory => Arc::new(Directory),
+        }
+    }
+}
+
+
+
+/// Return the key of the n-th chunk
+pub fn chunk_key(n: usize) -> String {
+    format!("{}.{}", TAG_KEY, n)
+}
+
+/// Stores tags in extended filesystem attributes. Small
+/// values are stored under a single key, larger ones are
+/// split into chunks of at most TAG_CHUNK_SIZE bytes.
+pub struct Xattr;
+
+impl Xattr {
+
+    /// List the chunks stored on a file by index and key
+    fn list_chunks(path: &Path) -> Res<Vec<(usize, OsString)>> {
+        let prefix = format!("{}.", TAG_KEY);
+        Ok(xattr::list(path)?
+            .filter_map(|key| {
+                let index = key.to_str()
+                    .filter(|k| k.starts_with(&prefix))
+                    .and_then(|k| k[prefix.len()..].parse::<usize>().ok());
+                index.map(|i| (i, key))
+            })
+            .collect())
+    }
+
+    /// Remove all chunks with an index of n or higher
+    fn purge_chunks(path: &Path, n: usize) -> Res<()> {
+        for (i, key) in Self::list_chunks(path)? {
+            if i >= n { xattr::remove(path, key)?; }
+        }
+        Ok(())
+    }
+
+    /// Remove the single-key value, so it exists
+    fn purge_key(path: &Path) -> Res<()> {
+        if xattr::get(path, TAG_KEY)?.is_some() {
+            xattr::remove(path, TAG_KEY)?;
+        }
+        Ok(())
+    }
+}
+
+impl TagStore for Xattr {
+    fn changes_ctime(&self) -> bool { true }
+
+    /// We prefer the single-key layout and fall back to
+    /// concatenating the chunks <TAG_KEY>.0..N
+    fn read(&self, path: &Path) -> Res<Option<Vec<u8>>> {
+        if let Some(raw) = xattr::get(path, TAG_KEY)? {
+            return Ok(Some(raw))
+        }
+        let mut raw: Option<Vec<u8>> = None;
+        let mut n = 0;
+        while let Some(chunk) = xattr::get(path, chunk_key(n))? {
+            raw.get_or_insert_with(Vec::new).extend(chunk);
+            n += 1;
+        }
+        Ok(raw)
+    }
+
+    /// Stale data from the other layout is removed
+    /// after writing
+    fn write(&self, path: &Path, raw: &[u8]) -> Res<()> {
+        if raw.len() <= TAG_CHUNK_SIZE {
+            xattr::set(path, TAG_KEY, raw)?;
+            Self::purge_chunks(path, 0)?;
+        } else {
+            let mut n = 0;
+            for chunk in raw.chunks(TAG_CHUNK_SIZE) {
+                xattr::set(path, chunk_key(n), chunk)?;
+                n += 1;
+            }
+            Self::purge_chunks(path, n)?;
+            Self::purge_key(path)?;
+        }
+        Ok(())
+    }
+
+    fn purge(&self, path: &Path) -> Res<()> {
+        Self::purge_key(path)?;
+        Self::purge_chunks(path, 0)
+    }
+}
+
+/// Split a path into its parent directory and file name
+fn split_path(path: &Path) -> Res<(&Path, &str)> {
+    let unsupported = || E::UnsupportedPath { path: path.to_string_lossy().into() };
+    let parent = path.parent().ok_or_else(unsupported)?;
+    let name = path.file_name().and_then(|n| n.to_str()).ok_or_else(unsupported)?;
+    Ok((parent, name))
+}
+
+/// Read a file, treating a missing file as empty
+fn read_optional(path: &Path) -> Res<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(raw) => Ok(Some(raw)),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a file, so it exists
+fn remove_optional(path: &Path) -> Res<()> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        r => Ok(r?),
+    }
+}
+
+/// Write a file atomically. The contents go to a new
+/// file in the same directory first, which then replaces
+/// the file, so readers never see it half written.
+fn write_atomic(path: &Path, contents: &[u8]) -> Res<()> {
+    use std::{fs::OpenOptions, io::Write, process, sync::atomic::{AtomicUsize, Ordering}};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let (_, name) = split_path(path)?;
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let temp = path.with_file_name(format!("{}.{}.{}{}", name, process::id(), n, TEMP_SUFFIX));
+    let written = OpenOptions::new().write(true).create_new(true).open(&temp)
+        .and_then(|mut file| { file.write_all(contents)?; file.sync_all() })
+        .and_then(|_| fs::rename(&temp, path));
+    if written.is_err() { let _ = fs::remove_file(&temp); }
+    Ok(written?)
+}
+
+/// Stores tags in a hidden '.<name>.tags' file next to
+/// each file. Works on any filesystem we can write to.
+pub struct Sidecar;
+
+impl Sidecar {
+    /// Return the sidecar path for a file
+    pub fn sidecar(path: &Path) -> Res<PathBuf> {
+        let (parent, name) = split_path(path)?;
+        Ok(parent.join(format!(".{}{}", name, SIDECAR_SUFFIX)))
+    }
+}
+
+impl TagStore for Sidecar {
+    fn read(&self, path: &Path) -> Res<Option<Vec<u8>>> {
+        read_optional(&Self::sidecar(path)?)
+    }
+
+    fn write(&self, path: &Path, raw: &[u8]) -> Res<()> {
+        Ok(fs::write(Self::sidecar(path)?, raw)?)
+    }
+
+    fn purge(&self, path: &Path) -> Res<()> {
+        remove_optional(&Self::sidecar(path)?)
+    }
+
+    fn is_internal(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.starts_with('.') && n.ends_with(SIDECAR_SUFFIX))
+            .unwrap_or(false)
+    }
+}
+
+/// Stores the tags of all files in a directory in a
+/// single hidden '.tags' file, mapping file names to
+/// their encoded tag data.
+pub struct Directory;
+
+impl Directory {
+
+    /// Read the directories sidecar
+    fn entries(sidecar: &Path) -> Res<HashMap<String, String>> {
+        match read_optional(sidecar)? {
+            Some(raw) => Ok(serde_json::from_slice(&raw)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Write the directories sidecar, removing it once empty
+    fn store(sidecar: &Path, entries: &HashMap<String, String>) -> Res<()> {
+        if entries.len() == 0 {
+            remove_optional(sidecar)
+        } else {
+            write_atomic(sidecar, &serde_json::to_vec_pretty(entries)?)
+        }
+    }
+}
+
+impl TagStore for Directory {
+    fn read(&self, path: &Path) -> Res<Option<Vec<u8>>> {
+        let (parent, name) = split_path(path)?;
+        let mut entries = Self::entries(&parent.join(DIRECTORY_SIDECAR))?;
+        Ok(entries.remove(name).map(|e| e.into_bytes()))
+    }
+
+    fn write(&self, path: &Path, raw: &[u8]) -> Res<()> {
+        let (parent, name) = split_path(path)?;
+        let sidecar = parent.join(DIRECTORY_SIDECAR);
+        let mut entries = Self::entries(&sidecar)?;
+        entries.insert(name.into(), String::from_utf8(raw.to_vec())?);
+        Self::store(&sidecar, &entries)
+    }
+
+    fn purge(&self, path: &Path) -> Res<()> {
+        let (parent, name) = split_path(path)?;
+        let sidecar = parent.join(DIRECTORY_SIDECAR);
+        let mut entries = Self::entries(&sidecar)?;
+        if entries.remove(name).is_some() {
+            Self::store(&sidecar, &entries)?;
+        }
+        Ok(())
+    }
+
+    fn is_internal(&self, path: &Path) -> bool {
+        let temp = format!("{}.", DIRECTORY_SIDECAR);
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n == DIRECTORY_SIDECAR || (n.starts_with(&temp) && n.ends_with(TEMP_SUFFIX)))
+            .unwrap_or(false)
+    }
+}
+
+/// Selects the store responsible for a path: the store
+/// configured for the longest matching root, or the
+/// default store.
+#[derive(Clone)]
+pub struct Stores {
+    default: Arc<dyn TagStore>,
+    roots: Vec<(PathBuf, Arc<dyn TagStore>)>,
+}
+
+impl Stores {
+
+    /// Create a new selector using the given default
+    pub fn new(default: Kind) -> Self {
+        Self { default: default.store(), roots: Vec::new() }
+    }
+
+    /// Use a different store for everything below root
+    pub fn add_root(&mut self, root: &Path, kind: Kind) {
+        let root = root.canonicalize().unwrap_or(root.to_owned());
+        self.roots.push((root, kind.store()));
+    }
+
+    /// Select the store responsible for the path
+    pub fn select(&self, path: &Path) -> Arc<dyn TagStore> {
+        if self.roots.len() == 0 {
+            return self.default.clone()
+        }
+        let absolute = if path.is_relative() {
+            std::env::current_dir().map(|d| d.join(path)).unwrap_or(path.to_owned())
+        } else {
+            path.to_owned()
+        };
+        self.roots.iter()
+            .filter(|(root, _)| absolute.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(_, store)| store.clone())
+            .unwrap_or(self.default.clone())
+    }
+
+    /// Indicates if the path is used internally by its store
+    pub fn is_internal(&self, path: &Path) -> bool {
+        self.select(path).is_internal(path)
+    }
+}
+
+impl std::fmt::Debug for Stores {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let roots: Vec<&PathBuf> = self.roots.iter().map(|(root, _)| root).collect();
+        f.debug_struct("Stores").field("roots", &roots).finish()
+    }
+}
+
+lazy_static! {
+    /// The stores in use by this process
+    static ref STORES: RwLock<Stores> = RwLock::new(Stores::new(Kind::default()));
+}
+
+/// Replace the stores in use by this process
+pub fn configure(stores: Stores) {
+    *STORES.write().expect("poisoned store lock") = stores;
+}
+
+/// The stores in use by this process
+pub fn current() -> Stores {
+    STORES.read().expect("poisoned store lock").clone()
+}
+
+/// Select the store responsible for the path
+pub fn select(path: &Path) -> Arc<dyn TagStore> {
+    STORES.read().expect("poisoned store lock").select(path)
+}
+
+/// Indicates if the path is used internally by its store
+pub fn is_internal(path: &Path) -> bool {
+    STORES.read().expect("poisoned store lock").is_internal(path)
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+    use crate::util::testing;
+
+    /// Create an empty directory with the given files
+    fn directory(files: &[&str]) -> (testing::TempDir, PathBuf) {
+        let (tmp, dir) = testing::temp_dir();
+        for file in files { fs::write(dir.join(file), file).unwrap(); }
+        (tmp, dir)
+    }
+
+    #[test]
+    fn check_sidecar() {
+        let (_tmp, dir) = directory(&["a", "b"]);
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        assert_eq!(Sidecar.read(&a).unwrap(), None);
+        Sidecar.write(&a, b"foo").unwrap();
+        Sidecar.write(&b, b"bar").unwrap();
+        assert!(dir.join(".a.tags").is_file());
+        assert_eq!(Sidecar.read(&a).unwrap(), Some(b"foo".to_vec()));
+        assert_eq!(Sidecar.read(&b).unwrap(), Some(b"bar".to_vec()));
+        Sidecar.purge(&a).unwrap();
+        Sidecar.purge(&a).unwrap();
+        assert!(!dir.join(".a.tags").exists());
+        assert_eq!(Sidecar.read(&a).unwrap(), None);
+        assert_eq!(Sidecar.read(&b).unwrap(), Some(b"bar".to_vec()));
+    }
+
+    #[test]
+    fn check_directory() {
+        let (_tmp, dir) = directory(&["a", "b"]);
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        assert_eq!(Directory.read(&a).unwrap(), None);
+        Directory.write(&a, b"foo").unwrap();
+        Directory.write(&b, b"bar").unwrap();
+        assert_eq!(Directory.read(&a).unwrap(), Some(b"foo".to_vec()));
+        assert_eq!(Directory.read(&b).unwrap(), Some(b"bar".to_vec()));
+        Directory.purge(&a).unwrap();
+        assert_eq!(Directory.read(&a).unwrap(), None);
+        assert_eq!(Directory.read(&b).unwrap(), Some(b"bar".to_vec()));
+        Directory.purge(&b).unwrap();
+        assert!(!dir.join(DIRECTORY_SIDECAR).exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn check_internal() {
+        assert_eq!(Sidecar.is_internal(Path::new("/music/.song.mp3.tags")), true);
+        assert_eq!(Sidecar.is_internal(Path::new("/music/song.mp3")), false);
+        assert_eq!(Sidecar.is_internal(Path::new("/music/song.tags")), false);
+        assert_eq!(Directory.is_internal(Path::new("/music/.tags")), true);
+        assert_eq!(Directory.is_internal(Path::new("/music/.song.mp3.tags")), false);
+        assert_eq!(Directory.is_internal(Path::new("/music/.tags.42.0.tmp")), true);
+        assert_eq!(Directory.is_internal(Path::new("/music/.tags.tmp.mp3")), false);
+        assert_eq!(Xattr.is_internal(Path::new("/music/.tags")), false);
+    }
+}
@@ -1,11 +1,13 @@
 use super::{import::*, api};
-use crate::model::tag;
+use crate::{model::tag, expression::Value};
 use internship::IStr;
 
 /// A tag is just a String with a few invariants.
 /// Since we expect to store many thousand instances
 /// of the same Tag, it is worthwhile to deduplicate
 /// the strings by interning them.
+/// Tags of the form key=value carry a value, which
+/// we interpret as a number if possible.
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub struct Tag (IStr);
 
@@ -21,6 +23,21 @@ impl Tag {
         Ok(Self(IStr::new(api::sanitize(name)?)))
     }
 
+    /// Return the key of a key=value tag, or the whole tag
+    pub fn key(&self) -> &str {
+        api::split_value(self.as_str()).0
+    }
+
+    /// Return the value of a key=value tag, so it has one
+    pub fn value(&self) -> Option<&str> {
+        api::split_value(self.as_str()).1
+    }
+
+    /// Return the typed value of a key=value tag
+    pub fn typed_value(&self) -> Option<Value> {
+        self.value().map(Value::parse)
+    }
+
     pub fn ident<'a>(&'a self) -> tag::Ident<'a> {
         tag::Ident { name: self.as_str() }
     }
@@ -28,6 +28,10 @@ pub mod error {
         UnknownIntId { id: i64 },
         #[fail(display = "unknown id: '{}'", id)]
         UnknownStrId { id: String },
+
+        // root errors
+        #[fail(display = "already indexed: '{}'", path)]
+        AlreadyIndexed { path: String },
     }
 }
 
@@ -37,28 +41,55 @@ pub mod api {
     pub use super::{Query, update, query::{self, Pipeline, Forcings, collect}, tag};
 
     /// The database connection is the only
-    /// persistent state need in our api struct.
+    /// persistent state need in our api struct,
+    /// besides an optional vocabulary of tags.
     pub struct DatabaseLayer {
         pub connection: db::Connection,
+        vocabulary: Option<Vocabulary>,
     }
 
     impl DatabaseLayer {
 
         /// Create a new api instance
         pub fn new(connection: db::Connection) -> Self {
-            Self { connection }
+            Self { connection, vocabulary: None }
+        }
+
+        /// Check added tags against the vocabulary,
+        /// including the tags in the index if configured
+        pub fn set_vocabulary(&mut self, vocabulary: Option<Vocabulary>) -> Res<()> {
+            self.vocabulary = match vocabulary {
+                Some(mut v) => { v.extend_from_database(self)?; Some(v) },
+                None => None,
+            };
+            Ok(())
+        }
+
+        /// Return the vocabulary, so there is one
+        pub fn vocabulary(&self) -> Option<&Vocabulary> {
+            self.vocabulary.as_ref()
         }
 
         /// Update the database by scanning the given paths recursively
-        pub fn update(&self, paths: &Vec<&str>) -> Res<()> {
-            update::api::run(paths, &self.connection)
+        pub fn update(&self, paths: &Vec<&str>) -> Res<update::Report> {
+            self.update_with(paths, &update::Settings::default())
+        }
+
+        /// Update the database using the given settings,
+        /// returning the changes
+        pub fn update_with(&self, paths: &Vec<&str>, settings: &update::Settings) -> Res<update::Report> {
+            if settings.batch > 0 {
+                update::api::run_batched(paths, settings, &self.connection)
+            } else {
+                update::api::run(paths, settings, &self.connection)
+            }
         }
 
         /// Enforce the configured database conventions
         pub fn enforce(&self, convention: &Vec<Convention>, commit: bool) -> Res<Vec<FieldReport>> {
             let mut reports = Vec::new();
             for convention in convention.iter() {
-                reports.push(convention.enforce(&self, commit)?);
+                reports.push(convention.enforce(self, commit)?);
             }
             Ok(reports)
         }
@@ -66,7 +97,7 @@ pub mod api {
         /// Run a query and map a single action over all files
         pub fn query_map(&self, pipeline: Pipeline, action: CommandAction, commit: bool) -> Res<Summary> {
             let command = Command::from_command_action(pipeline, &action);
-            Ok(command.run(&self, commit)?)
+            command.run(self, commit)
         }
 
         /// Since query results come in different shapes depending
@@ -82,7 +113,7 @@ pub mod api {
 
         /// Create a new query, and run it
         pub fn query<'q>(&self, pipeline: &Pipeline, forcings: query::Forcings) -> Res<query::Results<'q>> {
-            pipeline.forced_query(forcings, &self).execute()
+            pipeline.forced_query(forcings, self).execute()
         }
 
         /// Forget any unused tags
@@ -117,10 +148,30 @@ pub mod api {
             tag::api::query_all_tags_mapped(&self.connection)
         }
 
+        /// Return the indexed paths outside of all given roots
+        pub fn query_outside(&self, roots: &Vec<&str>) -> Res<Vec<String>> {
+            File::query_outside(roots, &self.connection)
+        }
+
+        /// Move the files indexed below one path to another,
+        /// returning how many were moved
+        pub fn move_root(&self, from: &str, to: &str) -> Res<i64> {
+            use super::error::Error as E;
+            self.connection.get().transaction::<_, Error, _>(|| {
+                if File::count_below(to, &self.connection)? > 0 {
+                    return Err(E::AlreadyIndexed { path: to.into() }.into())
+                }
+                Roots::relocate(from, to, &self.connection)?;
+                let moved = File::count_below(to, &self.connection)?;
+                info!("MOVE: {} File(s)", moved);
+                Ok(moved)
+            })
+        }
+
         /// Forget files by id
         pub fn forget(&self, files: &Vec<Fid>) -> Res<usize> {
             info!("DELETE: {} File(s)", files.len());
-            Ok(File::delete_ids(files, &self.connection)?)
+            File::delete_ids(files, &self.connection)
         }
     }
 }
@@ -141,8 +141,9 @@ impl Collector<Res<String>> for SerializePlain {
     }
 }
 
+/// The serialized shape of a file and it's tags
 #[derive(Serialize)]
-struct SerialFile<'q> {
+pub struct SerialFile<'q> {
     path: &'q str,
     tags: Vec<&'q str>,
 }
@@ -157,11 +158,35 @@ impl<'q> SerialFile<'q> {
                 .collect()
         }
     }
+    pub fn from_file(file: &'q attr::File) -> Self {
+        let mut tags: Vec<&'q str> = file.iter()
+            .map(|t| t.as_str())
+            .filter(|t| attr::api::ghostbuster(t).is_some())
+            .collect();
+        tags.sort();
+        Self { path: file.path_str(), tags }
+    }
     pub fn to_plain(&self) -> String {
         format!("{}\n {}", self.path, self.tags.join("\n * "))
     }
 }
 
+/// An owned SerialFile, e.g. for reading edited files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SerialFileBuf {
+    pub path: String,
+    pub tags: Vec<String>,
+}
+
+impl<'q> From<SerialFile<'q>> for SerialFileBuf {
+    fn from(file: SerialFile<'q>) -> Self {
+        Self {
+            path: file.path.into(),
+            tags: file.tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+}
+
 pub struct SerializeYaml;
 impl Collector<Res<String>> for SerializeYaml {
     fn forcings(&self) -> Forcings { Forcings::new().mapped() }
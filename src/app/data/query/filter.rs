@@ -3,7 +3,7 @@ use crate::{dsl::{filter as filter_dsl}, expression::Ast};
 use std::{cell::RefCell, process::Command};
 
 pub trait Filter {
-    fn filter<I>(&self, iter: I) -> Vec<Fid>
+    fn filter<I>(&self, iter: I) -> Res<Vec<Fid>>
     where
         I: Iterator<Item=Fid>;
 }
@@ -25,20 +25,20 @@ impl<'q> DslFilter<'q> {
         }
     }
 
-    fn filter_item(&self, file: FileView<'q>) -> bool {
+    fn filter_item(&self, file: FileView<'q>) -> Res<bool> {
         self.dsl.evaluate(self.ast, &self.context, &file)
-            .expect("filter error")
     }
 }
 
 impl<'q> Filter for DslFilter<'q> {
-    fn filter<I>(&self, iter: I) -> Vec<Fid>
+    fn filter<I>(&self, mut iter: I) -> Res<Vec<Fid>>
     where
         I: Iterator<Item=Fid>
     {
-        iter.filter(|i|
-            self.filter_item(self.maps.file(*i).unwrap())
-        ).collect()
+        iter.try_fold(Vec::new(), |mut accu, i| {
+            if self.filter_item(self.maps.file(i)?)? { accu.push(i) }
+            Ok(accu)
+        })
     }
 }
 
@@ -54,7 +54,7 @@ impl<'q> PipeFilter<'q> {
 }
 
 impl<'q> Filter for PipeFilter<'q> {
-    fn filter<I>(&self, iter: I) -> Vec<Fid>
+    fn filter<I>(&self, iter: I) -> Res<Vec<Fid>>
     where
         I: Iterator<Item=Fid>
     {
@@ -83,13 +83,13 @@ impl<'q> Filter for PipeFilter<'q> {
         let output = process.wait_with_output()
             .expect("failed to read stdout");
 
-        String::from_utf8_lossy(&output.stdout)
-            .split("\n")
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split('\n')
             .fold(Vec::new(), |_acc, path|{
                 if path.len() > 0 {
                     //acc.push(*self.maps.fids().by_alt(path).expect(&format!("unknown file: '{}'", path)));
                 }
                 _acc
-            })
+            }))
     }
 }
@@ -5,8 +5,8 @@ use crate::{
         file::{self, FileExt}
     },
     util::collections,
-    expression::Parameters,
-    app::attr::Tag
+    expression::{Parameters, Value},
+    app::attr::{Tag, api}
 };
 use owning_ref::OwningHandle;
 
@@ -30,6 +30,12 @@ impl<'q> collections::HasId<file::Ident<'q>> for file::Borrow<'q>
 const TAGS_LEN: &'static str = "tags.len";
 const PATH_LEN: &'static str = "path.len";
 const FILE_ID: &'static str = "file.id";
+const SIZE: &'static str = "size";
+const MTIME: &'static str = "mtime";
+const CTIME: &'static str = "ctime";
+const UID: &'static str = "uid";
+const GID: &'static str = "gid";
+const MODE: &'static str = "mode";
 
 /// A lot of our types have underlying
 /// columns. We implement this trait to reduce
@@ -61,17 +67,19 @@ pub trait Columnar {
 pub struct Columns {
     fcol: Vec<FCol>,
     tcol: Vec<TCol>,
+    mcol: Vec<MCol>,
     map: Vec<Ids>,
 }
 
 impl Columns {
-    pub fn from_cols(fcol: Vec<FCol>, tcol: Vec<TCol>, map: Vec<Ids>) -> Self {
-        Self { fcol, tcol, map }
+    pub fn from_cols(fcol: Vec<FCol>, tcol: Vec<TCol>, mcol: Vec<MCol>, map: Vec<Ids>) -> Self {
+        Self { fcol, tcol, mcol, map }
     }
     /// Shrink the internal buffers to fit
     pub fn shrink_to_fit(&mut self) {
         self.fcol.shrink_to_fit();
         self.tcol.shrink_to_fit();
+        self.mcol.shrink_to_fit();
         self.map.shrink_to_fit();
     }
 }
@@ -87,18 +95,18 @@ pub trait Viewable<'q> {
     fn tag_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q>;
     fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q>;
     fn file_iter(&'q self) -> Box<dyn Iterator<Item=file::Borrow<'q>> + 'q> {
-        box MapFileIter::from_iter(&self.maps(), self.file_view())
+        box MapFileIter::from_iter(self.maps(), self.file_view())
             .map(|f| f.as_borrow())
     }
     fn file_view_iter(&'q self) -> Box<dyn Iterator<Item=FileView<'q>> + 'q> {
-        box MapFileIter::from_iter(&self.maps(), self.file_view())
+        box MapFileIter::from_iter(self.maps(), self.file_view())
     }
     fn tag_iter(&'q self) -> Box<dyn Iterator<Item=tag::Borrow<'q>> + 'q> {
-        box MapTagIter::from_iter(&self.maps(), self.tag_view())
+        box MapTagIter::from_iter(self.maps(), self.tag_view())
             .map(|t| t.as_borrow())
     }
     fn tag_view_iter(&'q self) -> Box<dyn Iterator<Item=TagView<'q>> + 'q> {
-        box MapTagIter::from_iter(&self.maps(), self.tag_view())
+        box MapTagIter::from_iter(self.maps(), self.tag_view())
     }
     fn file_count(&'q self) -> usize {
         self.maps().fids().len()
@@ -115,6 +123,8 @@ pub struct Maps<'q> {
     fids: OneToOneFid<'q>,
     tids: OneToOneTid<'q>,
     mtom: ManyToManyIds,
+    meta: HashMap<Fid, &'q MCol>,
+    keys: HashSet<&'q str>,
 }
 
 impl<'q> Maps<'q> {
@@ -126,6 +136,8 @@ impl<'q> Maps<'q> {
             fids: OneToOneFid::new(),
             tids: OneToOneTid::new(),
             mtom: ManyToManyIds::new(),
+            meta: HashMap::new(),
+            keys: HashSet::new(),
         }
     }
 
@@ -134,9 +146,12 @@ impl<'q> Maps<'q> {
             fids: OneToOneFid::with_capacity(cols.tcol.len()),
             tids: OneToOneTid::with_capacity(cols.fcol.len()),
             mtom: mtom,
+            meta: HashMap::with_capacity(cols.mcol.len()),
+            keys: HashSet::new(),
         };
         cols.file_iter().for_each(|f| { this.add_file(f); });
         cols.tag_iter().for_each(|t| { this.add_tag(t); });
+        cols.mcol.iter().for_each(|m| { this.meta.insert(m.0, m); });
         this.shrink_to_fit();
         this
     }
@@ -145,6 +160,8 @@ impl<'q> Maps<'q> {
         self.fids.shrink_to_fit();
         self.tids.shrink_to_fit();
         self.mtom.shrink_to_fit();
+        self.meta.shrink_to_fit();
+        self.keys.shrink_to_fit();
     }
 
     pub fn fids(&self) -> &OneToOneFid<'q> { &self.fids }
@@ -160,6 +177,9 @@ impl<'q> Maps<'q> {
     /// Insert a tag into the underlying maps
     #[inline(always)]
     pub fn add_tag(&mut self, t: tag::Borrow<'q>) {
+        if let (key, Some(_)) = api::split_value(t.name) {
+            self.keys.insert(key);
+        }
         self.tids.map(t.id, t);
     }
 
@@ -180,19 +200,20 @@ impl<'q> Maps<'q> {
     pub fn file(&'q self, id: Fid) -> Res<FileView<'q>> {
         let file = self.fids.by_uid(id)?;
         let tids = self.mtom.get_rs(id)?;
-        Ok(FileView { id, maps: &self, path: file.path, kind: file.kind, tids })
+        let meta = self.meta.get(&id).copied();
+        Ok(FileView { id, maps: self, path: file.path, kind: file.kind, tids, meta })
     }
 
     /// View a tag by id
     pub fn tag(&'q self, id: Tid) -> Res<TagView<'q>> {
         let name = self.tids.by_uid(id)?.name;
         let fids = self.mtom.get_ls(id)?;
-        Ok(TagView { id, maps: &self, name, fids })
+        Ok(TagView { id, maps: self, name, fids })
     }
 }
 
 impl<'q> Viewable<'q> for Maps<'q> {
-    fn maps(&'q self) -> &'q Maps<'q> { &self }
+    fn maps(&'q self) -> &'q Maps<'q> { self }
     fn tag_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.tids.iter().map(|t| t.0) }
     fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.fids.iter().map(|f| f.0) }
 }
@@ -238,6 +259,7 @@ pub struct FileView<'q> {
     path: &'q str,
     tids: &'q HashSet<Tid>,
     maps: &'q Maps<'q>,
+    meta: Option<&'q MCol>,
 }
 
 impl<'q> FileExt for FileView<'q> {
@@ -276,7 +298,35 @@ impl<'q> FileView<'q> {
 
     /// Return an iterator over this files tags
     pub fn iter(&self) -> iter::MapTagIter<'q> {
-        iter::MapTagIter::from_iter(self.maps, self.tids.iter().map(|t| *t))
+        iter::MapTagIter::from_iter(self.maps, self.tids.iter().copied())
+    }
+
+    /// Return one of this files size, times in seconds,
+    /// owner and mode, if it is known
+    pub fn meta(&self, name: &str) -> Option<i64> {
+        let m = self.meta?;
+        match name {
+            SIZE => m.1,
+            MTIME => m.2.map(|t| t / 1_000_000_000),
+            CTIME => m.3.map(|t| t / 1_000_000_000),
+            UID => m.4,
+            GID => m.5,
+            MODE => m.6,
+            _ => None,
+        }
+    }
+
+    /// Render one of this files size, times, owner and mode
+    /// the way the stat namespace matches it: times as
+    /// local dates and the mode in octal
+    pub fn meta_text(&self, name: &str) -> Option<String> {
+        use chrono::{Local, TimeZone};
+        let value = self.meta(name)?;
+        Some(match name {
+            MTIME | CTIME => Local.timestamp_opt(value, 0).single()?.format("%Y-%m-%d %H:%M:%S").to_string(),
+            MODE => format!("{:o}", value),
+            _ => value.to_string(),
+        })
     }
 
     /// Turn this view into the simpler Borrow
@@ -289,15 +339,34 @@ impl<'q> FileView<'q> {
     }
 }
 
-/// A files parameters can be used as lhs in comparison filters
+/// A files parameters can be used as lhs in comparison filters.
+/// Besides the built-in parameters, every key=value tag
+/// exports its value under its key.
 impl<'q> Parameters for FileView<'q> {
-    fn parameters(&self) -> HashMap<&'static str, usize> {
+    fn parameters<'p>(&'p self) -> HashMap<&'p str, Value> {
         let mut map = HashMap::new();
-        map.insert(TAGS_LEN, self.tids.len() - 1);
-        map.insert(PATH_LEN, self.path.len());
-        map.insert(FILE_ID, self.id() as usize);
+        for tag in self.iter() {
+            if let (key, Some(value)) = api::split_value(tag.name()) {
+                map.insert(key, Value::parse(value));
+            }
+        }
+        map.insert(TAGS_LEN, Value::Integer(self.tids.len() as i64 - 1));
+        map.insert(PATH_LEN, Value::Integer(self.path.len() as i64));
+        map.insert(FILE_ID, Value::Integer(self.id()));
+        for name in [SIZE, MTIME, CTIME, UID, GID].iter() {
+            if let Some(value) = self.meta(name) { map.insert(*name, Value::Integer(value)); }
+        }
+        // Modes compare in octal, like ?[mode == 644]
+        if let Some(mode) = self.meta(MODE) { map.insert(MODE, Value::parse(&format!("{:o}", mode))); }
         map
     }
+
+    /// Built-in parameters and the keys of any queried
+    /// key=value tag are known, other files may carry them
+    fn is_known(&self, variable: &str) -> bool {
+        [TAGS_LEN, PATH_LEN, FILE_ID, SIZE, MTIME, CTIME, UID, GID, MODE].contains(&variable)
+            || self.maps.keys.contains(variable)
+    }
 }
 
 /// A borrowed view of a tag and it's associated files
@@ -336,7 +405,7 @@ impl<'q> TagView<'q> {
     /// Return an iterator over this tags associated files
     #[inline(always)]
     pub fn iter(&self) -> iter::MapFileIter<'q> {
-        iter::MapFileIter::from_iter(self.maps, self.fids.iter().map(|f| *f))
+        iter::MapFileIter::from_iter(self.maps, self.fids.iter().copied())
     }
 
     /// Turn this view into the simpler Borrow
@@ -38,7 +38,7 @@ pub mod prelude {
         #[inline(always)] pub fn mapped(self)                   -> Self { Forcings(self.0 | (1u8 << Forcing::Mapped as u8)) }
         #[inline(always)] pub fn filtered(self)                 -> Self { Forcings(self.mapped().0 | (1u8 << Forcing::Filtered as u8)) }
         #[inline(always)] pub fn piped(self)                    -> Self { Forcings(self.mapped().0 | (1u8 << Forcing::Piped as u8)) }
-        #[inline(always)] pub fn has_none(&self)                -> bool { (self.0 == 0u8) }
+        #[inline(always)] pub fn has_none(&self)                -> bool { self.0 == 0u8 }
         #[inline(always)] pub fn has_mapped(&self)              -> bool { (self.0 & (1u8 << Forcing::Mapped as u8)) != 0u8}
         #[inline(always)] pub fn has_filtered(&self)            -> bool { (self.0 & (1u8 << Forcing::Filtered as u8)) != 0u8}
         #[inline(always)] pub fn has_piped(&self)               -> bool { (self.0 & (1u8 << Forcing::Piped as u8)) != 0u8}
@@ -65,15 +65,23 @@ pub use export::*;
 pub mod api {
 
     use super::{import::*};
-    use crate::{dsl, db::wrangle::*, model::{tag, file_tag, prelude::Ids}};
+    use crate::{dsl, db::wrangle::*, model::{file, tag, file_tag, prelude::Ids}};
 
     /// When the user queries all files doing so directly is
-    /// more efficient
-    pub fn query_all(c: &db::Connection) -> Res<Vec<Ids>> {
+    /// more efficient. Offline files are left out unless
+    /// requested.
+    pub fn query_all(offline: bool, c: &db::Connection) -> Res<Vec<Ids>> {
         let rows: Vec<Ids> = profile!("all", {
-            file_tags::table
-                .select(file_tag::IDS)
-                .get_results(c.get())?
+            if offline {
+                file_tags::table
+                    .select(file_tag::IDS)
+                    .get_results(c.get())?
+            } else {
+                file_tags::table
+                    .select(file_tag::IDS)
+                    .filter(file_tags::file_id.ne_all(files::table.filter(files::offline.eq(true)).select(files::id)))
+                    .get_results(c.get())?
+            }
         });
         Ok(rows)
     }
@@ -106,8 +114,10 @@ pub mod api {
 
     /// The core query functionality: builds an aggregate
     /// SQL command from the compiled Expression and runs
-    /// the query.
-    pub fn query_dsl(exp: &CompiledExpression, c: &db::Connection) -> Res<Vec<Ids>> {
+    /// the query. Offline files are left out unless
+    /// requested.
+    pub fn query_dsl(exp: &CompiledExpression, offline: bool, c: &db::Connection) -> Res<Vec<Ids>> {
+        let states = if offline { vec![false, true] } else { vec![false] };
         let (dsl, context) = (dsl::query::Dsl::new(), RefCell::new(dsl::query::Context::new(c)));
         profile!("query dsl", { c.get().transaction::<_, Error, _>(|| {
             let expression = profile!("evaluate", {
@@ -116,6 +126,7 @@ pub mod api {
             let fids: Vec<Fid> = profile!("subselect", {
                 files::table
                     .filter(&expression)
+                    .filter(files::offline.eq_any(states))
                     .select(files::id)
                     .get_results(c.get())?
             });
@@ -129,7 +140,7 @@ pub mod api {
         })})
     }
 
-    pub fn query_columns<'a, T>(fids: T, tids: T, c: &db::Connection) -> Res<(Vec<FCol>, Vec<TCol>)>
+    pub fn query_columns<'a, T>(fids: T, tids: T, c: &db::Connection) -> Res<(Vec<FCol>, Vec<TCol>, Vec<MCol>)>
     where
         T: AsInExpression<BigInt> + Iterator<Item=&'a i64> + ExactSizeIterator,
         <T as AsInExpression<BigInt>>::InExpression: QueryFragment<Sqlite>,
@@ -139,12 +150,14 @@ pub mod api {
     {
         let flen = fids.len();
         let tlen = tids.len();
-        let fcol: Vec<FCol> = profile!("files", {
+        let (fcol, mcol): (Vec<FCol>, Vec<MCol>) = profile!("files", {
             info!("naming {} Files", flen);
             files::table
-                .select((files::id, files::path, files::kind))
+                .select((file::ids(), file::meta()))
                 .filter(files::id.eq_any(fids))
-                .get_results(c.get())?
+                .load::<(FCol, MCol)>(c.get())?
+                .into_iter()
+                .unzip()
         });
         let tcol: Vec<TCol> = profile!("tags", {
             info!("naming {} Tags", tlen);
@@ -157,7 +170,7 @@ pub mod api {
             "bug: inconsistent query results");
         assert_eq!(tcol.len(), tlen,
             "bug: inconsistent query results");
-        Ok((fcol, tcol))
+        Ok((fcol, tcol, mcol))
     }
 
     /// Takes raw data returned by the query dsl and
@@ -173,8 +186,8 @@ pub mod api {
                     (fids, tids)
                 })
         });
-        let (fcol, tcol) = query_columns(fids.iter(), tids.iter(), c)?;
-        Ok(Columns::from_cols(fcol, tcol, map))
+        let (fcol, tcol, mcol) = query_columns(fids.iter(), tids.iter(), c)?;
+        Ok(Columns::from_cols(fcol, tcol, mcol, map))
     }
 
     /// Takes raw data returned by the query dsl and
@@ -193,8 +206,8 @@ pub mod api {
                     (fids, tids, mtom)
                 })
         });
-        let (fcol, tcol) = query_columns(fids.iter(), tids.iter(), c)?;
-        Ok((Columns::from_cols(fcol, tcol, map), mtom))
+        let (fcol, tcol, mcol) = query_columns(fids.iter(), tids.iter(), c)?;
+        Ok((Columns::from_cols(fcol, tcol, mcol, map), mtom))
     }
 
     /// The core query functionality: builds an aggregate
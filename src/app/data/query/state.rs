@@ -13,8 +13,8 @@ pub struct Raw {
 impl Raw {
     pub fn from_query(dbq: &DatabaseQuery) -> Res<Self> {
         let data = match &dbq.pipeline.query {
-            Some(query) => api::query_dsl(&query, &dbq.api.connection)?,
-            None => api::query_all(&dbq.api.connection)?
+            Some(query) => api::query_dsl(query, dbq.pipeline.includes_offline(), &dbq.api.connection)?,
+            None => api::query_all(dbq.pipeline.includes_offline(), &dbq.api.connection)?
         };
         Ok(Self { data })
     }
@@ -110,7 +110,7 @@ impl<'q> Filtered<'q> {
             })?;
         let mut fids =
             DslFilter::new(mapped.maps.inner(), ast)
-                .filter(mapped.maps.fids().iter().map(|e| e.0));
+                .filter(mapped.maps.fids().iter().map(|e| e.0))?;
         fids.shrink_to_fit();
         Ok(Self { maps: mapped.maps, fids })
     }
@@ -119,7 +119,7 @@ impl<'q> Filtered<'q> {
 impl<'q> Viewable<'q> for Filtered<'q> {
     fn maps(&'q self) -> &'q Maps<'q> { self.maps.maps() }
     fn tag_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { self.maps.tag_view() }
-    fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.fids.iter().map(|e| *e) }
+    fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.fids.iter().copied() }
     fn file_count(&self) -> usize { self.maps.fids().len() }
 }
 
@@ -142,7 +142,7 @@ impl<'q> Piped<'q> {
             })?;
         let mut fids =
             PipeFilter::new(filtered.maps.inner(), pipe)
-                .filter(filtered.fids.iter().map(|e| *e));
+                .filter(filtered.fids.iter().copied())?;
         fids.shrink_to_fit();
         Ok(Self { maps: filtered.maps, fids })
     }
@@ -155,7 +155,7 @@ impl<'q> Piped<'q> {
             })?;
         let mut fids =
             PipeFilter::new(mapped.maps.inner(), pipe)
-                .filter(mapped.maps.fids().iter().map(|e| e.0));
+                .filter(mapped.maps.fids().iter().map(|e| e.0))?;
         fids.shrink_to_fit();
         Ok(Self { maps: mapped.maps, fids })
     }
@@ -164,7 +164,7 @@ impl<'q> Piped<'q> {
 impl<'q> Viewable<'q> for Piped<'q> {
     fn maps(&'q self) -> &'q Maps<'q> { self.maps.maps() }
     fn tag_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { self.maps.tag_view() }
-    fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.fids.iter().map(|e| *e) }
+    fn file_view(&'q self) -> Box<dyn Iterator<Item=Fid> + 'q> { box self.fids.iter().copied() }
     fn file_count(&self) -> usize { self.maps.fids().len() }
 }
 
@@ -230,7 +230,7 @@ impl<'q> Results<'q> {
             Self::Unassociated(inner) => Ok(box inner.tag_iter()),
             Self::Unmapped(inner) => Ok(box inner.tag_iter()),
             Self::Mapped(inner) => Ok(box inner.tag_iter()),
-            _ => Err(E::WrongState { state: self.name().into(), operation: "tag_iter()".into() }.into()),
+            _ => Err(E::WrongState { state: self.name().into(), operation: "tag_iter()".into() }),
         }
     }
 
@@ -238,14 +238,14 @@ impl<'q> Results<'q> {
         match &self {
             Self::Mapped(inner) => Ok(box inner.file_view_iter()),
             Self::Filtered(inner) => Ok(box inner.file_view_iter()),
-            _ => Err(E::WrongState { state: self.name().into(), operation: "file_view_iter()".into() }.into()),
+            _ => Err(E::WrongState { state: self.name().into(), operation: "file_view_iter()".into() }),
         }
     }
 
     pub fn tag_view_iter(&'q self) -> Result<Box<dyn Iterator<Item=TagView<'q>> + 'q>, E> {
         match &self {
             Self::Mapped(inner) => Ok(box inner.tag_view_iter()),
-            _ => Err(E::WrongState { state: self.name().into(), operation: "tag_view_iter()".into() }.into()),
+            _ => Err(E::WrongState { state: self.name().into(), operation: "tag_view_iter()".into() }),
         }
     }
 }
@@ -373,31 +373,37 @@ pub struct Pipeline {
     query: Option<CompiledExpression>,
     filter: Option<CompiledExpression>,
     pipe: Option<String>,
+    offline: bool,
 }
 
 impl Pipeline {
 
     /// Create a new query in it's initial state.
     pub fn new(query: Option<CompiledExpression>, filter: Option<CompiledExpression>, pipe: Option<String>) -> Self {
-        Self { query, filter, pipe }
+        Self { query, filter, pipe, offline: false }
     }
 
     /// Create a new pipeline from buffers.
     pub fn from_pipeline(pipeline: config::PipelineBuf) -> Res<Self> {
-        Self::from_strings(pipeline.query, pipeline.filter, pipeline.pipe)
+        Ok(Self::from_strings(pipeline.query, pipeline.filter, pipeline.pipe)?.with_offline(pipeline.offline))
     }
 
     /// Create a new pipeline from strings.
     pub fn from_strings(query: Option<String>, filter: Option<String>, pipe: Option<String>) -> Res<Self> {
         let (query, filter) = CompiledExpression::compile(query, filter)?;
-        Ok(Self { query, filter, pipe })
+        Ok(Self { query, filter, pipe, offline: false })
+    }
+
+    /// Include files on unmounted volumes.
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline; self
     }
 
     /// Get this pipelines minimum forcings.
     pub fn forcings(&self) -> Forcings {
         let mut f = Forcings::new();
-        if let Some(_) = self.filter { f = f.mapped().filtered() }
-        if let Some(_) = self.pipe   { f = f.mapped().piped() }
+        if self.filter.is_some() { f = f.mapped().filtered() }
+        if self.pipe.is_some()   { f = f.mapped().piped() }
         f
     }
 
@@ -413,6 +419,12 @@ impl Pipeline {
         &self.filter
     }
 
+    /// Indicates if this pipeline includes offline files.
+    #[inline(always)]
+    pub fn includes_offline(&self) -> bool {
+        self.offline
+    }
+
     /// Get this pipelines inner pipe expression.
     #[inline(always)]
     pub fn get_pipe(&self) -> &Option<String> {
@@ -421,11 +433,11 @@ impl Pipeline {
 
     /// Process this query with forcings.
     pub fn forced_query<'e, 'q>(&'e self, forcings: Forcings, api: &'e DatabaseLayer) -> Query<'e, 'q> {
-        Query::new(&self, forcings, api)
+        Query::new(self, forcings, api)
     }
 
     /// Process this query without forcings.
     pub fn query<'e, 'q>(&'e self, api: &'e DatabaseLayer) -> Query<'e, 'q> {
-        Query::new(&self, Forcings::new(), api)
+        Query::new(self, Forcings::new(), api)
     }
 }
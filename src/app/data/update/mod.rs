@@ -1,6 +1,7 @@
 pub mod scan;
 pub mod state;
 pub mod diff;
+pub mod watch;
 
 pub mod import {
     pub use super::super::import::*;
@@ -12,51 +13,141 @@ pub mod export {
     pub use super::scan::*;
     pub use super::state::*;
     pub use super::diff::*;
+    pub use super::watch::*;
 }
 pub use export::*;
 
+pub mod error {
+    #[derive(Debug, Fail)]
+    pub enum Error {
+        #[fail(display = "dry run, changes rolled back")]
+        DryRun,
+        #[fail(display = "skipped {} path(s), changes rolled back", count)]
+        Skipped { count: usize },
+    }
+}
+
 pub mod api {
 
     use super::import::*;
-    use super::export::*;
+    use super::{export::*, error::{Error as E}};
     use crate::{
-        model::{export::*, prelude::Ids, file, tag, file_tag},
-        app::{attr::{File as Attributes}, data::query},
+        model::{export::*, prelude::Ids, progress::Progress, file, tag, file_tag},
+        app::{attr::{self, File as Attributes, Stores}, data::query},
     };
 
-    fn query_scanned_file_ids(scan: &mut Scan, c: &db::Connection) -> Res<Vec<Fid>> {
+    fn query_scanned_files(scan: &mut Scan, c: &db::Connection) -> Res<Vec<file::Stats>> {
+        let roots = Roots::query(c)?;
         let mut scanned: Boolean =
-            box files::path.eq_any(scan.take_files());
+            roots.at_any(scan.take_files().iter().map(|f| f.as_str()));
         for d in scan.take_directories() {
-            let mut l = d.clone();
-            l.push(std::path::MAIN_SEPARATOR);
-            l.push('%');
-            scanned = box scanned.or(files::path.eq(d).or(files::path.like(l)));
+            scanned = box scanned.or(roots.below(&d));
         }
-        Ok(files::table.select(files::id).filter(scanned).get_results(c.get())?)
+        Ok(files::table.select(file::stats()).filter(scanned).get_results(c.get())?)
     }
 
-    fn scan_database_and_filesystem(paths: &Vec<&str>, c: &db::Connection) -> Res<(Vec<Attributes>, query::Columns, query::ManyToManyIds)> {
-        let mut scan = Scan::scan(paths);
+    /// A file is unchanged if its ctime, mtime and size match
+    /// the ones we stored. Only stores which bump the ctime
+    /// on write can be trusted, others are always re-read,
+    /// as are files indexed before modes were recorded.
+    fn is_unchanged(known: &file::Stats, stores: &Stores) -> bool {
+        if known.ctime.is_none() || known.mode.is_none() { return false }
+        if !stores.select(Path::new(&known.path)).changes_ctime() { return false }
+        match util::file::stat(&known.path) {
+            Some(s) => Some(s.ctime) == known.ctime && Some(s.mtime) == known.mtime && Some(s.size) == known.size,
+            None => false,
+        }
+    }
+
+    /// A vanished file is offline rather than gone if it
+    /// was below a missing library root, or if its closest
+    /// remaining ancestor is on another device than it was,
+    /// i.e. its volume is not mounted.
+    fn is_offline(known: &file::Stats, settings: &Settings) -> bool {
+        let path = Path::new(&known.path);
+        if settings.roots.iter().any(|r| path.starts_with(r) && !r.exists()) { return true }
+        let device = match known.device { Some(device) => device, None => return false };
+        path.ancestors().skip(1)
+            .find(|a| a.exists())
+            .and_then(|a| util::file::identity(&a.to_string_lossy()))
+            .map(|i| i.0 != device)
+            .unwrap_or(false)
+    }
+
+    fn scan_database_and_filesystem(mut scan: Scan, settings: &Settings, c: &db::Connection) -> Res<(Vec<Attributes>, Scanned, query::Columns, query::ManyToManyIds)> {
         profile!("query scan", { c.get().transaction::<_, Error, _>(|| {
+            let known = query_scanned_files(&mut scan, c)?;
+            let (found, vanished): (Vec<&file::Stats>, Vec<&file::Stats>) = {
+                let found: HashSet<&str> = scan.candidates().iter().map(|p| p.as_str()).collect();
+                known.iter().partition(|k| found.contains(k.path.as_str()))
+            };
+            let unchanged: HashSet<String> =
+                if settings.full { HashSet::new() }
+                else {
+                    found.iter()
+                        .filter(|k| is_unchanged(k, scan.stores()))
+                        .map(|k| k.path.clone())
+                        .collect()
+                };
+            scan.read(&unchanged);
+            // Files on unmounted volumes are kept offline,
+            // those we found again are back online.
+            let online: Vec<(Fid, String)> = found.iter().filter(|k| k.offline).map(|k| (k.id, k.path.clone())).collect();
+            let offline: Vec<&file::Stats> = vanished.into_iter().filter(|k| is_offline(k, settings)).collect();
+            // Files we failed to scan keep their tags, just
+            // like unchanged and offline ones.
+            let errors = scan.take_errors();
+            let kept: HashSet<String> = {
+                let skipped: HashSet<&Path> = errors.iter().map(|e| Path::new(&e.path)).collect();
+                let offline: HashSet<&str> = offline.iter().map(|k| k.path.as_str()).collect();
+                known.iter()
+                    .filter(|k| unchanged.contains(&k.path) || offline.contains(k.path.as_str()) || Path::new(&k.path).ancestors().any(|a| skipped.contains(a)))
+                    .map(|k| k.path.clone())
+                    .collect()
+            };
+            let offline: Vec<(Fid, String)> = offline.into_iter().filter(|k| !k.offline).map(|k| (k.id, k.path.clone())).collect();
+            let changed: Vec<Fid> =
+                known.iter()
+                    .filter(|k| !kept.contains(&k.path))
+                    .map(|k| k.id)
+                    .collect();
             let raw: Vec<Ids> =
                 file_tags::table
                     .select(file_tag::IDS)
-                    .filter(file_tags::file_id.eq_any(query_scanned_file_ids(&mut scan, c)?))
+                    .filter(file_tags::file_id.eq_any(&changed))
                     .get_results(c.get())?;
             let (columns, many_to_many) = query::api::query_associated(raw, c)?;
-            Ok((scan.take_attributes(), columns, many_to_many))
+            let scanned = Scanned {
+                known: known.into_iter().filter(|k| !kept.contains(&k.path)).map(|k| (k.path, k.id)).collect(),
+                stats: scan.take_stats(),
+                errors: errors,
+                offline: offline,
+                online: online,
+            };
+            Ok((scan.take_attributes(), scanned, columns, many_to_many))
         }) })
     }
 
+    /// Record the stats of known files we re-read, new and
+    /// moved files get theirs on insert
+    fn update_stats(attributes: &Vec<Attributes>, scanned: &Scanned, c: &db::Connection) -> Res<usize> {
+        let mut n = 0;
+        for a in attributes.iter() {
+            let path = a.path_str();
+            if let (Some(id), Some(stat)) = (scanned.known.get(path), scanned.stats.get(path)) {
+                n += File::update_stats(*id, stat, c)?;
+            }
+        }
+        Ok(n)
+    }
+
     /// Since not all tags appear on the scanned path
     /// we take special care not to insert duplicate tags
     /// or delete others unneccessarily.
     fn deduplicate_tags<'u>(diff: &'u Diff, c: &db::Connection) -> Res<(DiffedTags<'u>, DiffedTags<'u>, HashMap<String, Tid>)> {
         let (del, ins) = diff.tag_diff();
         let names =
-            ins.clone().chain(del.clone())
-                .map(|e| *e)
+            ins.clone().chain(del.clone()).copied()
                 .map(|t| t.name);
         let ddup =
             tags::table
@@ -67,52 +158,165 @@ pub mod api {
         Ok((del, ins, ddup))
     }
 
+    /// Create an insertable file, identified by device,
+    /// inode and optionally its contents
+    fn file_insert<'u>(path: &'u str, stat: Option<&util::file::Stat>, settings: &Settings) -> file::Insert<'u> {
+        let identity = util::file::identity(path);
+        file::Insert {
+            path: path,
+            kind: util::file::get_file_type(path).to_i64(),
+            device: identity.map(|i| i.0),
+            inode: identity.map(|i| i.1),
+            fingerprint: if settings.fingerprint { util::file::fingerprint(path) } else { None },
+            ctime: stat.map(|s| s.ctime),
+            mtime: stat.map(|s| s.mtime),
+            size: stat.map(|s| s.size),
+            uid: stat.map(|s| s.uid),
+            gid: stat.map(|s| s.gid),
+            mode: stat.map(|s| s.mode),
+        }
+    }
+
     /// Generate updates and deletes for files using
     /// the diff of filesystem and database
-    fn process_file_diff<'u>(diff: &'u Diff<'u>, ins: &mut Ins<'u>, del: &mut Del<'u>) {
+    fn process_file_diff<'u>(diff: &'u Diff<'u>, ins: &mut Ins<'u>, del: &mut Del<'u>, scanned: &Scanned, settings: &Settings, c: &db::Connection) -> Res<()> {
         let (fdel, fins) = diff.file_diff();
-        for f in fins { ins.files.push(file::Insert { path: f.path, kind: util::file::get_file_type(f.path).to_i64() }); }
+        for f in fins { ins.files.push(file_insert(f.path, scanned.stats.get(f.path), settings)); }
         for f in fdel { del.files.push(f.path); }
+        detect_moves(ins, del, c)
+    }
+
+    /// A file that disappeared from one path and appeared
+    /// on another has been moved, so we pair deleted and
+    /// inserted files by device and inode, or by their
+    /// fingerprint, and update their paths instead. Inodes
+    /// are reused, so without fingerprints the size and
+    /// mtime have to match too. Fingerprints only pair
+    /// files if no other deleted or inserted file shares
+    /// them, which rules out empty files as well.
+    fn detect_moves<'u>(ins: &mut Ins<'u>, del: &mut Del<'u>, c: &db::Connection) -> Res<()> {
+        if ins.files.len() == 0 || del.files.len() == 0 { return Ok(()) }
+        let known = File::query_paths(&del.files, c)?;
+        let mut pairs = Vec::new();
+        {
+            let mut by_identity = HashMap::new();
+            let mut by_fingerprint: HashMap<&str, Option<usize>> = HashMap::new();
+            for (n, f) in ins.files.iter().enumerate() {
+                if let (Some(device), Some(inode)) = (f.device, f.inode) {
+                    by_identity.insert((device, inode), n);
+                }
+                if let (Some(fingerprint), Some(size)) = (&f.fingerprint, f.size) {
+                    if size > 0 { by_fingerprint.entry(fingerprint.as_str()).and_modify(|n| *n = None).or_insert(Some(n)); }
+                }
+            }
+            let mut deleted: HashMap<&str, usize> = HashMap::new();
+            for f in known.iter().filter_map(|k| k.fingerprint.as_ref()) {
+                *deleted.entry(f.as_str()).or_insert(0) += 1;
+            }
+            let mut taken = HashSet::new();
+            for k in known.iter() {
+                let same_contents = |n: &usize| {
+                    let f = &ins.files[*n];
+                    match (&k.fingerprint, &f.fingerprint) {
+                        (Some(a), Some(b)) => a == b,
+                        _ => k.size.is_some() && k.size == f.size && k.mtime.is_some() && k.mtime == f.mtime,
+                    }
+                };
+                let found = match (k.device, k.inode) {
+                    (Some(device), Some(inode)) => by_identity.get(&(device, inode)).cloned().filter(same_contents),
+                    _ => None,
+                }.or_else(|| {
+                    k.fingerprint.as_deref()
+                        .filter(|f| deleted[f] == 1)
+                        .and_then(|f| by_fingerprint.get(f).cloned().flatten())
+                });
+                if let Some(n) = found {
+                    if taken.insert(n) { pairs.push((k.id, k.path.clone(), n)); }
+                }
+            }
+        }
+        let from: HashSet<String> = pairs.iter().map(|p| p.1.clone()).collect();
+        let mut to: HashMap<usize, (Fid, String)> = pairs.into_iter().map(|(id, path, n)| (n, (id, path))).collect();
+        let files = std::mem::take(&mut ins.files);
+        for (n, f) in files.into_iter().enumerate() {
+            match to.remove(&n) {
+                Some((id, from)) => ins.moves.push(file::Move { id: id, from: from, to: f }),
+                None => ins.files.push(f),
+            }
+        }
+        del.files.retain(|p| !from.contains(*p));
+        Ok(())
+    }
+
+    /// Record device and inode of files indexed before
+    /// we kept track of them
+    fn identify_files(attributes: &Vec<Attributes>, settings: &Settings, c: &db::Connection) -> Res<usize> {
+        let paths = attributes.iter().map(|a| a.path_str()).collect();
+        let unidentified = File::query_unidentified(&paths, c)?;
+        for f in unidentified.iter() {
+            File::update_identity(f.id, &file_insert(&f.path, None, settings), c)?;
+        }
+        Ok(unidentified.len())
     }
 
     /// Generate updates and deletes for tags using
     /// the diff of filesystem and database
     fn process_tag_diff<'u>(diff: &'u Diff<'u>, ins: &mut Ins<'u>, del: &mut Del<'u>, c: &db::Connection) -> Res<HashMap<String, Tid>> {
         let (tdel, tins, ddup) = deduplicate_tags(diff, c)?;
-        for t in tins { if !ddup.contains_key(t.name) { ins.tags.push(tag::Insert { name: t.name }); }}
+        for t in tins { if !ddup.contains_key(t.name) { ins.tags.push(tag::Insert { name: t.name, value: attr::api::split_value(t.name).1 }); }}
         for t in tdel { if !ddup.contains_key(t.name) { del.tags.push(t.name); }}
         Ok(ddup)
     }
 
     /// Generate updates and deletes for filetags using
     /// the diff of filesystem and database
-    fn process_filetag_diff(diff: &Diff, ins: &mut Ins, del: &mut Del, maps: &Maps) {
+    fn process_filetag_diff(diff: &Diff, ins: &mut Ins, del: &mut Del, maps: &Maps, report: &mut Report) {
         let get = |f, t| { (maps.fids().by_alt(f).unwrap(), maps.tids().by_alt(t).unwrap()) };
         let (pdel, pins) = diff.filetag_diff();
-        for (file, tag) in pins {
+        let pins: Vec<(Ids, (&str, &str))> = pins.map(|(file, tag)| {
             let (f, t) = get(*file, *tag);
-            ins.filetags.push(FileTag { file_id: *f, tag_id: *t });
-        }
-        for (file, tag) in pdel {
+            ((*f, *t), (file.path, tag.name))
+        }).collect();
+        let pdel: Vec<(Ids, (&str, &str))> = pdel.map(|(file, tag)| {
             let (f, t) = get(*file, *tag);
-            del.filetags.push((*f, *t));
+            ((*f, *t), (file.path, tag.name))
+        }).collect();
+        // Moved files keep their id, so their tags show
+        // up as both inserted and deleted.
+        let inserted: HashSet<Ids> = pins.iter().map(|e| e.0).collect();
+        let kept: HashSet<Ids> = pdel.iter().map(|e| e.0).filter(|e| inserted.contains(e)).collect();
+        for ((f, t), (path, name)) in pins.into_iter().filter(|e| !kept.contains(&e.0)) {
+            ins.filetags.push(FileTag { file_id: f, tag_id: t });
+            report.filetags.inserted.push((path.into(), name.into()));
+        }
+        for (ids, (path, name)) in pdel.into_iter().filter(|e| !kept.contains(&e.0)) {
+            del.filetags.push(ids);
+            report.filetags.deleted.push((path.into(), name.into()));
         }
     }
 
-    /// Insert, map and delete files
-    fn process_files<'u>(ins: &Ins, del: &Del, c: &db::Connection) -> Res<Vec<File>> {
+    /// Mark files which went offline or came back online
+    fn process_offline(scanned: &Scanned, c: &db::Connection) -> Res<()> {
+        File::set_offline(&scanned.offline.iter().map(|f| f.0).collect(), true, c)?;
+        File::set_offline(&scanned.online.iter().map(|f| f.0).collect(), false, c)?;
+        Ok(())
+    }
+
+    /// Delete, move and insert files
+    fn process_files(ins: &Ins, del: &Del, c: &db::Connection) -> Res<Vec<File>> {
         File::delete_paths(&del.files, c)?;
+        File::move_all(&ins.moves, c)?;
         File::insert_all(&ins.files, c)
     }
 
     /// Insert, map and delete tags
-    fn process_tags<'u>(ins: &Ins, del: &Del, c: &db::Connection) -> Res<Vec<Tag>> {
+    fn process_tags(ins: &Ins, del: &Del, c: &db::Connection) -> Res<Vec<Tag>> {
         Tag::delete_names(&del.tags, c)?;
         Tag::insert_all(&ins.tags, c)
     }
 
     /// Insert and delete filetags
-    fn process_filetags<'u>(ins: &Ins, del: &Del, c: &db::Connection) -> Res<()> {
+    fn process_filetags(ins: &Ins, del: &Del, c: &db::Connection) -> Res<()> {
         FileTag::insert_all(&ins.filetags, c)?;
         FileTag::delete_ids(&del.filetags, c)?;
         Ok(())
@@ -124,19 +328,36 @@ pub mod api {
     ///   3. Insert missing files and tags and map their IDs
     ///   4. Insert missing filetags (now we know the IDs)
     ///   5. Forget any items that exist in the db and not in the fs
-    pub fn run(paths: &Vec<&str>, c: &db::Connection) -> Res<()> {
-        profile!("transaction", { c.get().transaction::<_, Error, _>(|| {
-            let (attributes, columns, many_to_many) = profile!("queries", { scan_database_and_filesystem(paths, c)? });
+    /// Files that merely moved keep their ids. Files whose stats
+    /// did not change since the last update are skipped, unless
+    /// a full update was requested. A dry run rolls back the
+    /// transaction, still reporting what would have changed.
+    /// So does a strict update which skipped any path.
+    pub fn run(paths: &Vec<&str>, settings: &Settings, c: &db::Connection) -> Res<Report> {
+        run_scan(Scan::scan(paths, settings)?, settings, None, c)
+    }
+
+    /// Run the update routine for a Scan, recording the
+    /// given progress in the same transaction
+    fn run_scan(scan: Scan, settings: &Settings, progress: Option<&Progress>, c: &db::Connection) -> Res<Report> {
+        let mut report = Report::default();
+        let result = profile!("transaction", { c.get().transaction::<_, Error, _>(|| {
+            Roots::register_all(&settings.roots, c)?;
+            let (attributes, scanned, columns, many_to_many) = profile!("queries", { scan_database_and_filesystem(scan, settings, c)? });
             let mut maps = profile!("maps", { Maps::from_cols_and_mtom(&columns, many_to_many) });
             let diff = profile!("diff", { Diff::from_cols_and_attr(&columns, &attributes, &maps)? });
             let mut ins = Ins::new();
             let mut del = Del::new();
             let fins; let tins; let tdup;
             profile!("files", {
-                profile!("diff", { process_file_diff(&diff, &mut ins, &mut del) });
+                profile!("diff", { process_file_diff(&diff, &mut ins, &mut del, &scanned, settings, c)? });
                 profile!("sql", {
                     fins = process_files(&ins, &del, c)?;
                     for f in fins.iter() { maps.add_file(file::Borrow { id: f.id, path: &f.path, kind: util::file::get_file_type(&f.path).to_i64() }); }
+                    for m in ins.moves.iter() { maps.add_file(file::Borrow { id: m.id, path: m.to.path, kind: m.to.kind }); }
+                    profile!("identify", { identify_files(&attributes, settings, c)? });
+                    profile!("stats", { update_stats(&attributes, &scanned, c)? });
+                    profile!("offline", { process_offline(&scanned, c)? });
                 })
             });
             profile!("tags", {
@@ -148,16 +369,314 @@ pub mod api {
                 });
             });
             profile!("filetags", {
-                profile!("diff", { process_filetag_diff(&diff, &mut ins, &mut del, &maps) });
+                profile!("diff", { process_filetag_diff(&diff, &mut ins, &mut del, &maps, &mut report) });
                 profile!("sql", { process_filetags(&ins, &del, c)? });
             });
+            for m in ins.moves.iter() { info!("MOVE: {} -> {}", m.from, m.to.path); }
             info!("INSERT: {} File(s)", ins.files.len());
+            info!("MOVE: {} File(s)", ins.moves.len());
             info!("DELETE: {} File(s)", del.files.len());
+            info!("OFFLINE: {} File(s)", scanned.offline.len());
+            info!("ONLINE: {} File(s)", scanned.online.len());
             info!("INSERT: {} Tag(s)", ins.tags.len());
             info!("DELETE: {} Tag(s)", del.tags.len());
             info!("INSERT: {} FileTag(s)", ins.filetags.len());
             info!("DELETE: {} FileTag(s)", del.filetags.len());
+            let filetags = std::mem::take(&mut report.filetags);
+            report = Report {
+                filetags: filetags,
+                skipped: scanned.errors.clone(),
+                offline: scanned.offline.iter().map(|f| f.1.clone()).collect(),
+                online: scanned.online.iter().map(|f| f.1.clone()).collect(),
+                ..Report::from_ins_and_del(&ins, &del)
+            };
+            for e in scanned.errors.iter() { warn!("SKIP: {} ({})", e.path, e.message); }
+            if settings.strict && scanned.errors.len() > 0 { return Err(E::Skipped { count: scanned.errors.len() }.into()) }
+            if settings.dry_run { return Err(E::DryRun.into()) }
+            if let Some(progress) = progress { progress.save(c)?; }
             Ok(())
-        })})
+        })});
+        match result {
+            Ok(()) => Ok(Report { committed: true, ..report }),
+            Err(e) => match e.downcast::<E>() {
+                Ok(E::DryRun) | Ok(E::Skipped { .. }) => Ok(report),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Indicates if an indexed path vanished, or is ignored
+    /// or used by a tag store now
+    fn is_gone(path: &str, rules: &Rules, stores: &Stores) -> bool {
+        let path = Path::new(path);
+        match std::fs::symlink_metadata(path) {
+            Ok(m) => rules.is_excluded(path, m.is_dir()) || stores.is_internal(path),
+            Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+        }
+    }
+
+    /// Forget the files at or below root which are gone,
+    /// or mark them offline, one page at a time. Then
+    /// forget the progress.
+    fn prune(root: &str, rules: &Rules, settings: &Settings, c: &db::Connection) -> Res<Report> {
+        let mut report = Report { committed: !settings.dry_run, ..Report::default() };
+        let stores = settings.tag_stores();
+        let mut after = 0;
+        loop {
+            let page = File::query_below(root, after, settings.batch, c)?;
+            after = match page.last() { Some(f) => f.id, None => break };
+            let (offline, gone): (Vec<&file::Stats>, Vec<&file::Stats>) = page.iter()
+                .filter(|k| is_gone(&k.path, rules, &stores))
+                .partition(|k| is_offline(k, settings));
+            let offline: Vec<&file::Stats> = offline.into_iter().filter(|k| !k.offline).collect();
+            let gone: Vec<&str> = gone.iter().map(|k| k.path.as_str()).collect();
+            if !settings.dry_run {
+                File::set_offline(&offline.iter().map(|k| k.id).collect(), true, c)?;
+                File::delete_paths(&gone, c)?;
+            }
+            report.offline.extend(offline.into_iter().map(|k| k.path.clone()));
+            report.files.deleted.extend(gone.into_iter().map(String::from));
+        }
+        if !settings.dry_run { Progress::clear(root, c)?; }
+        info!("DELETE: {} vanished File(s)", report.files.deleted.len());
+        info!("OFFLINE: {} File(s)", report.offline.len());
+        Ok(report)
+    }
+
+    /// Update the given paths in batches of at most
+    /// settings.batch files, committing each batch along
+    /// with the last path it covered. An interrupted update
+    /// resumes after the last committed batch. Vanished
+    /// files are forgotten in a final pass, so moves are
+    /// only detected within a batch. Files below missing
+    /// library roots are kept offline.
+    pub fn run_batched(paths: &Vec<&str>, settings: &Settings, c: &db::Connection) -> Res<Report> {
+        let rules = Rules::new(&settings.ignore, &settings.traversal)?;
+        let mut report = Report { committed: true, ..Report::default() };
+        for path in paths.iter() {
+            let root = match PathBuf::from(path).canonicalize() {
+                Ok(root) => root,
+                // A missing library root is most likely not
+                // mounted, so its files go offline
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound && settings.roots.iter().any(|r| r == Path::new(path)) => {
+                    report.merge(prune(path, &rules, settings, c)?);
+                    continue
+                },
+                Err(e) => {
+                    report.skipped.push(ScanError::new(Path::new(path), Reason::from_io(&e), e.to_string()));
+                    continue
+                },
+            };
+            let key = root.to_string_lossy().to_string();
+            let after = Progress::query(&key, c)?.map(|p| PathBuf::from(p.path));
+            if let Some(ref after) = after { info!("resuming '{}' after '{}'", key, after.display()); }
+            let mut entries = Walk::stream(&root, &rules, after);
+            loop {
+                let batch: Vec<Result<PathBuf, ScanError>> = entries.by_ref().take(settings.batch).collect();
+                if batch.len() == 0 { break }
+                let progress = batch.iter().rev()
+                    .find_map(|e| e.as_ref().ok())
+                    .map(|p| Progress { root: key.clone(), path: p.to_string_lossy().into() });
+                let done = run_scan(Scan::from_entries(batch, settings)?, settings, progress.as_ref(), c)?;
+                let aborted = settings.strict && done.skipped.len() > 0;
+                report.merge(done);
+                if aborted { return Ok(report) }
+            }
+            report.merge(prune(&key, &rules, settings, c)?);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::{import::*, api, Settings, Report};
+    use crate::{model::File, app::attr, util::testing};
+    use std::fs;
+
+    /// Create an empty directory and a new database for a test
+    fn fixture() -> (testing::TempDir, PathBuf, db::Connection) {
+        let (tmp, dir) = testing::temp_dir();
+        fs::create_dir(dir.join("files")).unwrap();
+        let c = testing::database(&dir);
+        (tmp, dir.join("files"), c)
+    }
+
+    /// Add a tag to a file
+    fn tag(path: &Path, tag: &str) {
+        let mut file = attr::File::open(path.to_owned()).unwrap();
+        file.add(tag).unwrap();
+        file.save().unwrap();
+    }
+
+    /// Create a file with the given contents and tag
+    fn create(path: &Path, contents: &str, name: &str) {
+        fs::write(path, contents).unwrap();
+        tag(path, name);
+    }
+
+    /// The id of an indexed file
+    fn id_of(path: &Path, c: &db::Connection) -> Option<Fid> {
+        File::query_paths(&vec![path.to_str().unwrap()], c).unwrap().pop().map(|f| f.id)
+    }
+
+    #[test]
+    fn check_move() {
+        let (_tmp, dir, c) = fixture();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        create(&a, "a", "foo");
+        api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        let id = id_of(&a, &c).unwrap();
+        fs::rename(&a, &b).unwrap();
+        let report = api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        assert_eq!(report.moves.len(), 1);
+        assert_eq!(report.moves[0].from, a.to_str().unwrap());
+        assert_eq!(report.moves[0].to, b.to_str().unwrap());
+        assert!(report.files.inserted.is_empty() && report.files.deleted.is_empty());
+        assert!(report.filetags.inserted.is_empty() && report.filetags.deleted.is_empty());
+        assert_eq!(id_of(&a, &c), None);
+        assert_eq!(id_of(&b, &c), Some(id));
+    }
+
+    #[test]
+    fn check_reused_inode() {
+        let (_tmp, dir, c) = fixture();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        let settings = Settings { fingerprint: true, ..Settings::default() };
+        create(&a, "a", "foo");
+        api::run(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        let id = id_of(&a, &c).unwrap();
+        fs::remove_file(&a).unwrap();
+        create(&b, "b", "foo");
+        // Pretend b got the inode a had
+        let (device, inode) = util::file::identity(b.to_str().unwrap()).unwrap();
+        diesel::update(files::table.find(id))
+            .set((files::device.eq(device), files::inode.eq(inode)))
+            .execute(c.get()).unwrap();
+        let report = api::run(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        assert!(report.moves.is_empty());
+        assert_eq!(report.files.deleted, vec![a.to_str().unwrap()]);
+        assert_eq!(report.files.inserted, vec![b.to_str().unwrap()]);
+    }
+
+    #[test]
+    fn check_reused_inode_without_fingerprint() {
+        let (_tmp, dir, c) = fixture();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        create(&a, "a", "foo");
+        api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        let id = id_of(&a, &c).unwrap();
+        fs::remove_file(&a).unwrap();
+        create(&b, "bb", "foo");
+        // Pretend b got the inode a had
+        let (device, inode) = util::file::identity(b.to_str().unwrap()).unwrap();
+        diesel::update(files::table.find(id))
+            .set((files::device.eq(device), files::inode.eq(inode)))
+            .execute(c.get()).unwrap();
+        let report = api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        assert!(report.moves.is_empty());
+        assert_eq!(report.files.deleted, vec![a.to_str().unwrap()]);
+        assert_eq!(report.files.inserted, vec![b.to_str().unwrap()]);
+    }
+
+    #[test]
+    fn check_ambiguous_fingerprint() {
+        let (_tmp, dir, c) = fixture();
+        let settings = Settings { fingerprint: true, ..Settings::default() };
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        create(&dir.join("a"), "a", "foo");
+        create(&dir.join("empty"), "", "foo");
+        api::run(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        // Two copies of a and another empty file show up
+        // before the originals are gone.
+        for (name, contents) in &[("b", "a"), ("c", "a"), ("void", "")] {
+            create(&dir.join(name), contents, "foo");
+        }
+        fs::remove_file(dir.join("a")).unwrap();
+        fs::remove_file(dir.join("empty")).unwrap();
+        let mut report = api::run(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        assert!(report.moves.is_empty());
+        report.files.deleted.sort();
+        report.files.inserted.sort();
+        assert_eq!(report.files.deleted, vec![path("a"), path("empty")]);
+        assert_eq!(report.files.inserted, vec![path("b"), path("c"), path("void")]);
+    }
+
+    #[test]
+    fn check_unchanged() {
+        let (_tmp, dir, c) = fixture();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        create(&a, "a", "foo");
+        create(&b, "b", "foo");
+        tag(&b, "baz");
+        api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        // Tagging only touches the ctime of a, while b is
+        // left alone but lost a tag in the index.
+        tag(&a, "bar");
+        let dropped: Tid = tags::table.select(tags::id).filter(tags::name.eq("baz")).get_result(c.get()).unwrap();
+        diesel::delete(file_tags::table.find((id_of(&b, &c).unwrap(), dropped))).execute(c.get()).unwrap();
+        let touched = |report: &Report, path: &Path| report.filetags.inserted.iter().any(|(p, _)| p == path.to_str().unwrap());
+        let report = api::run(&vec![dir.to_str().unwrap()], &Settings::default(), &c).unwrap();
+        assert_eq!(report.filetags.inserted, vec![(a.to_str().unwrap().to_string(), "bar".to_string())]);
+        assert!(!touched(&report, &b));
+        let report = api::run(&vec![dir.to_str().unwrap()], &Settings { full: true, ..Settings::default() }, &c).unwrap();
+        assert!(!touched(&report, &a));
+        assert!(touched(&report, &b));
+    }
+
+    #[test]
+    fn check_resume() {
+        use crate::model::progress::Progress;
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let (_tmp, dir, c) = fixture();
+        let (a, b, invalid) = (dir.join("a"), dir.join("b"), dir.join(OsStr::from_bytes(b"b\xff")));
+        create(&a, "a", "foo");
+        create(&b, "b", "foo");
+        create(&dir.join("c"), "c", "foo");
+        fs::write(&invalid, "b").unwrap();
+        // The second batch skips a path, so a strict
+        // update stops after the first one.
+        let settings = Settings { batch: 2, strict: true, ..Settings::default() };
+        let report = api::run_batched(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        assert_eq!(report.committed, false);
+        assert_eq!(Progress::query(dir.to_str().unwrap(), &c).unwrap().map(|p| p.path), Some(a.to_str().unwrap().to_string()));
+        assert_eq!(id_of(&b, &c), None);
+        // Resuming leaves the first batch alone
+        fs::remove_file(&invalid).unwrap();
+        tag(&a, "bar");
+        let report = api::run_batched(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        assert_eq!(report.committed, true);
+        assert!(report.filetags.inserted.iter().all(|(p, _)| p != a.to_str().unwrap()));
+        assert!(id_of(&b, &c).is_some());
+        assert_eq!(Progress::query(dir.to_str().unwrap(), &c).unwrap(), None);
+        // A finished update starts over
+        let report = api::run_batched(&vec![dir.to_str().unwrap()], &settings, &c).unwrap();
+        assert_eq!(report.filetags.inserted, vec![(a.to_str().unwrap().to_string(), "bar".to_string())]);
+    }
+
+    #[test]
+    fn check_offline() {
+        let (_tmp, dir, c) = fixture();
+        let (root, away) = (dir.join("library"), dir.join("unmounted"));
+        fs::create_dir(&root).unwrap();
+        let a = root.join("a");
+        create(&a, "a", "foo");
+        for batch in &[0, 2] {
+            let settings = Settings { batch: *batch, roots: vec![root.clone()], ..Settings::default() };
+            let update = || if *batch > 0 { api::run_batched(&vec![root.to_str().unwrap()], &settings, &c) }
+                else { api::run(&vec![root.to_str().unwrap()], &settings, &c) }.unwrap();
+            let offline = || File::query_paths(&vec![a.to_str().unwrap()], &c).unwrap().pop().map(|f| f.offline);
+            update();
+            assert_eq!(offline(), Some(false));
+            fs::rename(&root, &away).unwrap();
+            let report = update();
+            assert!(report.offline.contains(&a.to_string_lossy().to_string()));
+            assert!(report.files.deleted.is_empty());
+            assert_eq!(offline(), Some(true));
+            fs::rename(&away, &root).unwrap();
+            let report = update();
+            assert!(report.online.contains(&a.to_string_lossy().to_string()));
+            assert_eq!(offline(), Some(false));
+        }
     }
 }
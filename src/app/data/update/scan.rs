@@ -1,113 +1,627 @@
-use super::import::*;
-use crate::app::{attr};
+use super::{import::*, state::Settings};
+use crate::app::{attr::{self, TagStore, Stores}};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+use ignore::{WalkBuilder, gitignore::{Gitignore, GitignoreBuilder}};
+
+/// The name of per-directory ignore files
+pub const IGNORE_FILE: &'static str = ".tagignore";
+
+/// The name of git's per-directory ignore files
+pub const GIT_IGNORE_FILE: &'static str = ".gitignore";
+
+/// How many files to read before decoding their tags
+const READ_CHUNK: usize = 4096;
+
+/// Which paths to leave out of a walk, in addition to
+/// those listed in IGNORE_FILE files
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    /// Gitignore-style patterns, matched anywhere
+    pub patterns: Vec<String>,
+    /// Honour .gitignore files as well
+    pub gitignore: bool,
+}
+
+/// How to traverse the filesystem. Depths are counted
+/// from the scanned directory, which has depth zero.
+#[derive(Debug, Clone, Default)]
+pub struct Traversal {
+    /// Follow symbolic links, loops are detected and links
+    /// into a scanned directory are skipped
+    pub follow_links: bool,
+    /// Don't descend into other filesystems
+    pub one_file_system: bool,
+    /// Skip entries deeper than this
+    pub max_depth: Option<usize>,
+    /// Skip entries less deep than this
+    pub min_depth: Option<usize>,
+}
+
+/// Why a path was skipped during a scan
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// We may not read the file or directory
+    PermissionDenied,
+    /// The file disappeared while we scanned it
+    NotFound,
+    /// The path is not valid UTF-8
+    InvalidPath,
+    /// The tags could not be decoded
+    InvalidTags,
+    /// Not a file type we know how to index
+    UnsupportedType,
+    /// A symbolic link pointing to one of its ancestors
+    Loop,
+    /// Any other I/O error
+    Io,
+}
+
+impl Reason {
+    /// The reason matching an I/O error
+    pub fn from_io(e: &std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => Reason::PermissionDenied,
+            std::io::ErrorKind::NotFound => Reason::NotFound,
+            _ => Reason::Io,
+        }
+    }
+}
+
+/// A path skipped during a scan. Indexed files at or below
+/// it are kept as they are.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanError {
+    pub path: String,
+    pub reason: Reason,
+    pub message: String,
+}
+
+impl ScanError {
+
+    /// Create a new ScanError
+    pub fn new(path: &Path, reason: Reason, message: String) -> Self {
+        Self { path: path.to_string_lossy().into(), reason: reason, message: message }
+    }
+
+    /// Create a new ScanError from an error of the walk
+    fn from_walk(root: &Path, e: ignore::Error) -> Self {
+        fn unwrap(e: &ignore::Error, path: Option<&Path>) -> (Option<PathBuf>, Reason) {
+            match e {
+                ignore::Error::WithPath { path, err } => unwrap(err, Some(path)),
+                ignore::Error::WithDepth { err, .. } => unwrap(err, path),
+                ignore::Error::WithLineNumber { err, .. } => unwrap(err, path),
+                ignore::Error::Loop { child, .. } => (Some(child.clone()), Reason::Loop),
+                ignore::Error::Io(e) => (path.map(PathBuf::from), Reason::from_io(e)),
+                _ => (path.map(PathBuf::from), Reason::Io),
+            }
+        }
+        let (path, reason) = unwrap(&e, None);
+        Self::new(path.as_deref().unwrap_or(root), reason, e.to_string())
+    }
+
+    /// Create a new ScanError from an error reading tags
+    fn from_read(path: &Path, e: Error) -> Self {
+        let reason = match e.downcast_ref::<std::io::Error>() {
+            Some(e) => Reason::from_io(e),
+            None => Reason::InvalidTags,
+        };
+        Self::new(path, reason, e.to_string())
+    }
+}
+
+/// Compiled ignore rules and traversal options, shared
+/// between walker threads
+#[derive(Clone)]
+pub struct Rules {
+    globs: Arc<Gitignore>,
+    gitignore: bool,
+    traversal: Traversal,
+}
+
+impl Rules {
+
+    /// Compile the given ignore settings
+    pub fn new(ignore: &Ignore, traversal: &Traversal) -> Res<Self> {
+        let mut builder = GitignoreBuilder::new("/");
+        for pattern in ignore.patterns.iter() {
+            builder.add_line(None, pattern)?;
+        }
+        Ok(Self {
+            globs: Arc::new(builder.build()?),
+            gitignore: ignore.gitignore,
+            traversal: traversal.clone(),
+        })
+    }
+
+    /// Indicates if the path is ignored by a global pattern
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.globs.matched(path, is_dir).is_ignore()
+    }
+
+    /// Indicates if the path or one of its ancestors is
+    /// ignored by a global pattern
+    pub fn is_ignored_below(&self, path: &Path, is_dir: bool) -> bool {
+        path.is_absolute() && self.globs.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+
+    /// Indicates if a walk would leave out an absolute path,
+    /// because it or one of its ancestors is ignored by a
+    /// global pattern or the ignore files above it. Nearer
+    /// ignore files take precedence, and IGNORE_FILE takes
+    /// precedence over .gitignore in the same directory.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if !path.is_absolute() { return false }
+        if self.is_ignored_below(path, is_dir) { return true }
+        let matchers: Vec<Gitignore> = path.ancestors().skip(1)
+            .flat_map(|dir| self.ignore_files(dir))
+            .collect();
+        // A walk doesn't descend into ignored directories,
+        // so a nearer file can't whitelist their entries.
+        path.ancestors().enumerate().any(|(n, p)| {
+            matchers.iter()
+                .filter(|m| p.starts_with(m.path()) && p != m.path())
+                .map(|m| m.matched(p, n > 0 || is_dir))
+                .find(|m| !m.is_none())
+                .map(|m| m.is_ignore())
+                .unwrap_or(false)
+        })
+    }
+
+    /// Indicates if the path is an ignore file these rules
+    /// honour
+    pub fn is_ignore_file(&self, path: &Path) -> bool {
+        match path.file_name() {
+            Some(name) => name == IGNORE_FILE || (self.gitignore && name == GIT_IGNORE_FILE),
+            None => false,
+        }
+    }
+
+    /// The ignore files in a directory, by precedence
+    fn ignore_files(&self, dir: &Path) -> Vec<Gitignore> {
+        let names: &[&str] = if self.gitignore { &[IGNORE_FILE, GIT_IGNORE_FILE] } else { &[IGNORE_FILE] };
+        names.iter()
+            .map(|name| dir.join(name))
+            .filter(|file| file.is_file())
+            .map(|file| {
+                let (matcher, e) = Gitignore::new(&file);
+                if let Some(e) = e { warn!("invalid ignore file '{}': {}", file.display(), e) }
+                matcher
+            })
+            .collect()
+    }
+
+    /// A walk which applies these rules and yields
+    /// entries sorted by name, up to the given depth.
+    /// Entries up to `after` are skipped, along with
+    /// whole directories that precede it.
+    fn walker(&self, root: &Path, max_depth: Option<usize>, after: Option<PathBuf>) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        let rules = self.clone();
+        builder
+            .standard_filters(false)
+            .git_ignore(self.gitignore)
+            .require_git(false)
+            .parents(true)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .follow_links(self.traversal.follow_links)
+            .same_file_system(self.traversal.one_file_system)
+            .max_depth(max_depth)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |e| {
+                let done = after.as_ref().map(|a| e.path() <= a.as_path() && !a.starts_with(e.path())).unwrap_or(false);
+                !done && !rules.is_ignored(e.path(), e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            });
+        builder
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self { globs: Arc::new(Gitignore::empty()), gitignore: false, traversal: Traversal::default() }
+    }
+}
 
 /// Stores the results of filesystem traversal
 /// Shoutout to the glorious burntsushi :)
-struct Walk {
+pub struct Walk {
     pub root: PathBuf,
     pub files: Vec<PathBuf>,
+    pub errors: Vec<ScanError>,
 }
 
 impl Walk {
 
-    /// Create and run a new Walk
+    /// Create and run a new Walk, honouring IGNORE_FILE
+    /// files only
     pub fn new(root: String) -> Self {
-        use walkdir::WalkDir;
+        Self::with_rules(root, &Rules::default())
+    }
+
+    /// Create and run a new Walk on as many threads as the
+    /// current thread pool has. Entries are sorted by path
+    /// afterwards, the order a walk on a single thread yields
+    /// them in, so the result is the same for any number of
+    /// threads. Only the root is canonicalized, entries keep
+    /// the path we found them at, so symbolic links aren't
+    /// resolved.
+    pub fn with_rules(root: String, rules: &Rules) -> Self {
+        use ignore::WalkState;
+        use std::sync::mpsc;
         profile!("walk", {
-            let files =
-                WalkDir::new(&root).into_iter()
-                    .filter_map(|e| e.ok())
-                    .filter_map(|e| e.path().canonicalize().ok())
-                    .collect();
+            let base = PathBuf::from(&root).canonicalize().unwrap_or_else(|_| PathBuf::from(&root));
+            let min_depth = rules.traversal.min_depth.unwrap_or(0);
+            let (tx, rx) = mpsc::channel();
+            rules.walker(&base, rules.traversal.max_depth, None)
+                .threads(rayon::current_num_threads())
+                .build_parallel()
+                .run(|| {
+                    let (tx, base) = (tx.clone(), &base);
+                    box move |e| {
+                        let entry = match e {
+                            Ok(e) if e.depth() < min_depth => return WalkState::Continue,
+                            Ok(e) => Ok(e.into_path()),
+                            Err(e) => Err(ScanError::from_walk(base, e)),
+                        };
+                        tx.send(entry).ok();
+                        WalkState::Continue
+                    }
+                });
+            drop(tx);
+            let (mut files, mut errors) = (Vec::new(), Vec::new());
+            for entry in rx {
+                match entry {
+                    Ok(path) => files.push(path),
+                    Err(e) => errors.push(e),
+                }
+            }
+            files.sort();
+            errors.sort_by(|a, b| Path::new(&a.path).cmp(Path::new(&b.path)));
             Self {
                 root: PathBuf::from(&root),
                 files: files,
+                errors: errors,
             }
         })
     }
+
+    /// Walk a canonical root on the calling thread, yielding
+    /// entries one at a time in the same order as a Walk,
+    /// which is the order of their paths. Entries up to and
+    /// including `after` are skipped.
+    pub fn stream(root: &Path, rules: &Rules, after: Option<PathBuf>) -> impl Iterator<Item=Result<PathBuf, ScanError>> {
+        let min_depth = rules.traversal.min_depth.unwrap_or(0);
+        let base = root.to_path_buf();
+        rules.walker(root, rules.traversal.max_depth, after.clone()).build()
+            .filter(move |e| match e {
+                Ok(e) => e.depth() >= min_depth && after.as_ref().map(|a| e.path() > a.as_path()).unwrap_or(true),
+                Err(_) => true,
+            })
+            .map(move |e| e.map(|e| e.into_path()).map_err(|e| ScanError::from_walk(&base, e)))
+    }
 }
 
 /// Stores the accumulated results of a filesystem scan
 pub struct Scan {
+    pool: ThreadPool,
+    rules: Rules,
+    stores: Stores,
     attributes: Vec<attr::File>,
+    candidates: Vec<String>,
     directories: Vec<String>,
     files: Vec<String>,
+    stats: HashMap<String, util::file::Stat>,
+    errors: Vec<ScanError>,
 }
 
 impl Scan {
 
-    /// Create a new Scan.
-    pub fn new() -> Self {
-        Self { attributes: Vec::new(), directories: Vec::new(), files: Vec::new() }
+    /// Create a new Scan, running on the given number of
+    /// threads, or one per CPU if zero.
+    pub fn new(threads: usize, rules: Rules, stores: Stores) -> Res<Self> {
+        Ok(Self {
+            pool: ThreadPoolBuilder::new().num_threads(threads).build()?,
+            rules: rules,
+            stores: stores,
+            attributes: Vec::new(),
+            candidates: Vec::new(),
+            directories: Vec::new(),
+            files: Vec::new(),
+            stats: HashMap::new(),
+            errors: Vec::new(),
+        })
     }
 
     /// Shrink the internal buffers to fit.
     pub fn shrink_to_fit(&mut self) {
         self.attributes.shrink_to_fit();
+        self.candidates.shrink_to_fit();
         self.directories.shrink_to_fit();
         self.files.shrink_to_fit();
     }
 
+    /// The tag stores files are read from.
+    pub fn stores(&self) -> &Stores {
+        &self.stores
+    }
+
+    /// The paths of all files found, read or not.
+    pub fn candidates(&self) -> &Vec<String> {
+        &self.candidates
+    }
+
     /// Take the files buffer from this Scan.
     pub fn take_files(&mut self) -> Vec<String> {
-        std::mem::replace(&mut self.files, Vec::new())
+        std::mem::take(&mut self.files)
     }
 
     /// Take the attributes buffer from this Scan.
     pub fn take_attributes(&mut self) -> Vec<attr::File> {
-        std::mem::replace(&mut self.attributes, Vec::new())
+        std::mem::take(&mut self.attributes)
     }
 
     /// Take the directories buffer from this Scan.
     pub fn take_directories(&mut self) -> Vec<String> {
-        std::mem::replace(&mut self.directories, Vec::new())
+        std::mem::take(&mut self.directories)
+    }
+
+    /// Take the paths skipped by this Scan.
+    pub fn take_errors(&mut self) -> Vec<ScanError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Take the stats of the files read by this Scan.
+    pub fn take_stats(&mut self) -> HashMap<String, util::file::Stat> {
+        std::mem::take(&mut self.stats)
     }
 
-    /// Scan a single file.
+    /// Scan a single file. Ignored files are looked up but
+    /// not read, so they are dropped from the index.
     fn scan_file(&mut self, file: String) {
-        if let Ok(file) = attr::File::open(PathBuf::from(file)) {
-            self.files.push(file.path_str().into());
-            self.attributes.push(file);
-        }
+        if self.rules.is_excluded(Path::new(&file), false) { return self.files.push(file) }
+        self.add_file(file);
+    }
+
+    /// Add a file which passed the ignore rules.
+    fn add_file(&mut self, file: String) {
+        if self.stores.is_internal(Path::new(&file)) { return }
+        self.files.push(file.clone());
+        self.candidates.push(file);
     }
 
     /// Scan a directory and it's descendants.
     fn scan_directory(&mut self, directory: String) {
-        let walk = Walk::new(directory);
-        self.attributes.extend(
-            walk.files.iter()
-                .filter_map(|e| {
-                    if let Some(s) = e.to_str() {
-                        if let Ok(f) = attr::File::open(PathBuf::from(s)) { return Some(f) }
-                    }
-                    None
-                })
-        );
+        let rules = &self.rules;
+        let walk = self.pool.install(|| Walk::with_rules(directory, rules));
+        let stores = &self.stores;
+        for e in walk.files.iter().filter(|e| !stores.is_internal(e)) {
+            match e.to_str() {
+                Some(s) => self.candidates.push(s.to_owned()),
+                None => self.errors.push(ScanError::new(e, Reason::InvalidPath, "path is not valid UTF-8".into())),
+            }
+        }
+        self.errors.extend(walk.errors);
         if let Some(s) = walk.root.to_str() {
             self.directories.push(s.to_owned())
         }
     }
 
-    /// Scan the given file or directory.
+    /// Scan the given file or directory. Absolute paths which
+    /// no longer exist are scanned as empty directories, so
+    /// anything indexed at or below them is dropped.
     fn scan_path(this: &mut Scan, path: &str) {
-        if let Ok(canonical) = PathBuf::from(path).canonicalize() {
-            if let Some(s) = canonical.to_str() {
-                if      canonical.is_file() { this.scan_file(s.to_string()) }
-                else if canonical.is_dir()  { this.scan_directory(s.to_string()) }
+        let path = PathBuf::from(path);
+        match path.canonicalize() {
+            Ok(canonical) => match canonical.to_str() {
+                Some(s) if canonical.is_dir() => this.scan_directory(s.to_string()),
+                Some(s) => this.scan_file(s.to_string()),
+                None => this.errors.push(ScanError::new(&canonical, Reason::InvalidPath, "path is not valid UTF-8".into())),
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound && path.is_absolute() => {
+                if let Some(s) = path.to_str() { this.directories.push(s.to_string()) }
+            },
+            Err(e) => this.errors.push(ScanError::new(&path, Reason::from_io(&e), e.to_string())),
+        }
+    }
+
+    /// Drop the files reached through a symbolic link whose
+    /// target lies below a scanned directory, so following
+    /// links doesn't index them twice. Targets elsewhere
+    /// keep the path of the link.
+    fn skip_linked(&mut self) {
+        let roots: Vec<&Path> = self.directories.iter().map(Path::new).collect();
+        let candidates = std::mem::take(&mut self.candidates);
+        self.candidates = self.pool.install(|| {
+            candidates.into_par_iter()
+                .filter(|c| match Path::new(c).canonicalize() {
+                    Ok(real) => real == Path::new(c) || !roots.iter().any(|r| real.starts_with(r)),
+                    Err(_) => true,
+                })
+                .collect()
+        });
+    }
+
+    /// Create a Scan of the given walk entries, without
+    /// descending into directories.
+    pub fn from_entries(entries: Vec<Result<PathBuf, ScanError>>, settings: &Settings) -> Res<Self> {
+        let mut this = Scan::new(settings.threads, Rules::default(), settings.tag_stores())?;
+        for e in entries {
+            match e {
+                Ok(path) => match path.to_str() {
+                    Some(s) => this.add_file(s.to_string()),
+                    None => this.errors.push(ScanError::new(&path, Reason::InvalidPath, "path is not valid UTF-8".into())),
+                },
+                Err(e) => this.errors.push(e),
             }
         }
+        Ok(this)
     }
 
     /// Scan the given paths and returing a new Scan instance.
-    pub fn scan(paths: &Vec<&str>) -> Self {
-        profile!("scan", { Self::new();
-            let mut this = Scan::new();
+    /// No tags are read until `read` is called.
+    pub fn scan(paths: &Vec<&str>, settings: &Settings) -> Res<Self> {
+        let rules = Rules::new(&settings.ignore, &settings.traversal)?;
+        profile!("scan", {
+            let mut this = Scan::new(settings.threads, rules, settings.tag_stores())?;
             for path in paths { Self::scan_path(&mut this, path); }
+            if settings.traversal.follow_links { this.skip_linked(); }
             info!("scanned {} Directories", this.directories.len());
-            info!("scanned {} Files", this.files.len());
-            info!("scanned {} Attributes", this.attributes.len());
+            info!("scanned {} Files", this.candidates.len());
+            info!("skipped {} Path(s)", this.errors.len());
             this.shrink_to_fit();
-            this
+            Ok(this)
         })
     }
+
+    /// Read the tags of all scanned files, except the
+    /// unchanged ones. Stats are taken before reading, so
+    /// a concurrent write is picked up by the next update.
+    /// Raw tag data is read on the thread pool, a chunk of
+    /// files at a time, and decoded on the calling thread,
+    /// in scan order, before the next chunk is read.
+    pub fn read(&mut self, unchanged: &HashSet<String>) {
+        profile!("read", {
+            let candidates: Vec<&String> = self.candidates.iter().filter(|p| !unchanged.contains(*p)).collect();
+            let stores = &self.stores;
+            for chunk in candidates.chunks(READ_CHUNK) {
+                let raw: Vec<(&String, Option<util::file::Stat>, Arc<dyn TagStore>, Result<Option<Vec<u8>>, ScanError>)> =
+                    profile!("raw", { self.pool.install(|| {
+                        chunk.par_iter()
+                            .map(|p| {
+                                let path = Path::new(p);
+                                let stat = util::file::stat(p);
+                                let store = stores.select(path);
+                                let raw = match util::file::file_type(p) {
+                                    Ok(Some(_)) => store.read(path).map_err(|e| ScanError::from_read(path, e)),
+                                    Ok(None) => Err(ScanError::new(path, Reason::UnsupportedType, "unsupported file type".into())),
+                                    Err(e) => Err(ScanError::new(path, Reason::from_io(&e), e.to_string())),
+                                };
+                                (*p, stat, store, raw)
+                            })
+                            .collect()
+                    }) });
+                for (p, stat, store, raw) in raw {
+                    let path = PathBuf::from(p);
+                    let file = raw.and_then(|raw| {
+                        attr::File::from_raw(path.clone(), store, raw).map_err(|e| ScanError::from_read(&path, e))
+                    });
+                    match file {
+                        Ok(file) => {
+                            if let Some(stat) = stat { self.stats.insert(p.clone(), stat); }
+                            self.attributes.push(file);
+                        },
+                        Err(e) => self.errors.push(e),
+                    }
+                }
+            }
+            info!("skipped {} unchanged Files", unchanged.len());
+            info!("scanned {} Attributes", self.attributes.len());
+            self.attributes.shrink_to_fit();
+        })
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+    use crate::util::testing;
+    use std::fs;
+
+    #[test]
+    fn check_ignore() {
+        let (_tmp, root) = testing::temp_dir();
+        for dir in &["src", "target", "node_modules/left-pad"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in &["src/main.rs", "target/main", "node_modules/left-pad/index.js", "notes.tmp"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE), "target/\n").unwrap();
+        let rules = Rules::new(&Ignore { patterns: vec!["node_modules".into(), "*.tmp".into()], gitignore: false }, &Traversal::default()).unwrap();
+        let walk = Walk::with_rules(root.to_string_lossy().into(), &rules);
+        let found: Vec<_> = walk.files.iter().map(|f| f.strip_prefix(&root).unwrap().to_path_buf()).collect();
+        let expected: Vec<PathBuf> = vec!["", ".tagignore", "src", "src/main.rs"].into_iter().map(PathBuf::from).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn check_ignore_file() {
+        let (_tmp, root) = testing::temp_dir();
+        for dir in &["a/b", "a/c"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in &["a/b/x", "a/b/y", "a/c/x"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        fs::write(root.join("a").join(IGNORE_FILE), "x\nc/\n").unwrap();
+        fs::write(root.join("a/b").join(IGNORE_FILE), "!x\ny\n").unwrap();
+        let rules = Rules::default();
+        assert_eq!(rules.is_excluded(&root.join("a/b/x"), false), false);
+        assert_eq!(rules.is_excluded(&root.join("a/b/y"), false), true);
+        assert_eq!(rules.is_excluded(&root.join("a/c"), true), true);
+        assert_eq!(rules.is_excluded(&root.join("a/c/x"), false), true);
+        let paths: Vec<String> = ["a/b/x", "a/b/y", "a/c/x"].iter().map(|p| root.join(p).to_string_lossy().into()).collect();
+        let mut scan = Scan::scan(&paths.iter().map(|p| p.as_str()).collect(), &Settings::default()).unwrap();
+        assert_eq!(scan.candidates(), &vec![paths[0].clone()]);
+        assert_eq!(scan.take_files(), paths);
+    }
+
+    #[test]
+    fn check_parallel_walk() {
+        let (_tmp, root) = testing::temp_dir();
+        for dir in &["a/b/c", "a.b", "d", "e/f"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in &["a/x", "a/b/x", "a/b/c/x", "a.b/x", "d/x", "e/f/x", "x"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for (min_depth, max_depth) in [(None, None), (Some(2), None), (None, Some(2)), (Some(1), Some(3))].iter().cloned() {
+            let traversal = Traversal { min_depth, max_depth, ..Traversal::default() };
+            let rules = Rules::new(&Ignore::default(), &traversal).unwrap();
+            let walk = pool.install(|| Walk::with_rules(root.to_string_lossy().into(), &rules));
+            let stream: Vec<PathBuf> = Walk::stream(&root, &rules, None).map(|e| e.unwrap()).collect();
+            assert_eq!(walk.files, stream);
+        }
+    }
+
+    #[test]
+    fn check_follow_links() {
+        use std::os::unix::fs::symlink;
+        let (_tmp, root) = testing::temp_dir();
+        let (_other, elsewhere) = testing::temp_dir();
+        fs::create_dir_all(root.join("real")).unwrap();
+        fs::write(root.join("real/x"), "x").unwrap();
+        fs::write(elsewhere.join("y"), "y").unwrap();
+        symlink(root.join("real"), root.join("inside")).unwrap();
+        symlink(&elsewhere, root.join("outside")).unwrap();
+        let settings = Settings { traversal: Traversal { follow_links: true, ..Traversal::default() }, ..Settings::default() };
+        let scan = Scan::scan(&vec![root.to_str().unwrap()], &settings).unwrap();
+        let found: Vec<_> = scan.candidates().iter().map(|f| Path::new(f).strip_prefix(&root).unwrap().to_path_buf()).collect();
+        let expected: Vec<PathBuf> = vec!["", "outside", "outside/y", "real", "real/x"].into_iter().map(PathBuf::from).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn check_internal() {
+        use crate::app::attr::store::Kind;
+        let (_tmp, root) = testing::temp_dir();
+        for dir in &["sidecar", "directory"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let mut stores = Stores::new(Kind::Xattr);
+        stores.add_root(&root.join("sidecar"), Kind::Sidecar);
+        stores.add_root(&root.join("directory"), Kind::Directory);
+        for dir in &["sidecar", "directory"] {
+            let file = root.join(dir).join("a");
+            fs::write(&file, "a").unwrap();
+            stores.select(&file).write(&file, b"foo").unwrap();
+        }
+        let settings = Settings { stores: Some(stores), ..Settings::default() };
+        let scan = Scan::scan(&vec![root.to_str().unwrap()], &settings).unwrap();
+        let found: Vec<_> = scan.candidates().iter().map(|f| Path::new(f).strip_prefix(&root).unwrap().to_path_buf()).collect();
+        let expected: Vec<PathBuf> = vec!["", "directory", "directory/a", "sidecar", "sidecar/a"].into_iter().map(PathBuf::from).collect();
+        assert_eq!(found, expected);
+    }
 }
@@ -1,9 +1,161 @@
-use super::import::*;
+use super::{import::*, scan::{Ignore, Traversal, ScanError}};
 use crate::model::{file, tag, FileTag};
+use crate::app::attr::store::{self, Stores};
 
-/// Stores Insertable values during update
+/// Settings for a single update
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Fingerprint file contents, so we can detect
+    /// files moved across filesystems
+    pub fingerprint: bool,
+    /// Re-read the tags of all files, even those that
+    /// did not change since the last update
+    pub full: bool,
+    /// The number of threads used for scanning, one
+    /// per CPU if zero
+    pub threads: usize,
+    /// Paths to leave out, already indexed ones are
+    /// dropped from the index
+    pub ignore: Ignore,
+    /// How to traverse directories
+    pub traversal: Traversal,
+    /// Compute the changes without committing them
+    pub dry_run: bool,
+    /// Roll back if any path was skipped
+    pub strict: bool,
+    /// Update in batches of this many files, if not zero
+    pub batch: usize,
+    /// Library roots, files below a missing one are kept
+    /// offline instead of being forgotten
+    pub roots: Vec<PathBuf>,
+    /// The tag stores to read from, those of the process
+    /// if unset
+    pub stores: Option<Stores>,
+}
+
+impl Settings {
+    /// The tag stores to read from
+    pub fn tag_stores(&self) -> Stores {
+        self.stores.clone().unwrap_or_else(store::current)
+    }
+}
+
+/// The files known to the database which were re-read
+/// during a scan, the stats of every file read, the paths
+/// we skipped and the files which went offline or came
+/// back online
+pub struct Scanned {
+    pub known: HashMap<String, Fid>,
+    pub stats: HashMap<String, util::file::Stat>,
+    pub errors: Vec<ScanError>,
+    pub offline: Vec<(Fid, String)>,
+    pub online: Vec<(Fid, String)>,
+}
+
+/// The inserted and deleted items of one kind
+#[derive(Debug, Default, Serialize)]
+pub struct Changes<T> {
+    pub inserted: Vec<T>,
+    pub deleted: Vec<T>,
+}
+
+/// A file indexed under a new path
+#[derive(Debug, Serialize)]
+pub struct Moved {
+    pub from: String,
+    pub to: String,
+}
+
+/// The changes made by an update, or those it would
+/// have made if it was rolled back. Filetags are (path,
+/// tag) pairs.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub committed: bool,
+    pub files: Changes<String>,
+    pub moves: Vec<Moved>,
+    pub tags: Changes<String>,
+    pub filetags: Changes<(String, String)>,
+    pub skipped: Vec<ScanError>,
+    pub offline: Vec<String>,
+    pub online: Vec<String>,
+}
+
+impl Report {
+
+    /// Collect the changes from the insert and delete
+    /// buffers, except filetags which only have ids here
+    pub fn from_ins_and_del(ins: &Ins, del: &Del) -> Self {
+        Self {
+            committed: false,
+            files: Changes {
+                inserted: ins.files.iter().map(|f| f.path.into()).collect(),
+                deleted: del.files.iter().map(|f| f.to_string()).collect(),
+            },
+            moves: ins.moves.iter().map(|m| Moved { from: m.from.clone(), to: m.to.path.into() }).collect(),
+            tags: Changes {
+                inserted: ins.tags.iter().map(|t| t.name.into()).collect(),
+                deleted: del.tags.iter().map(|t| t.to_string()).collect(),
+            },
+            filetags: Changes::default(),
+            skipped: Vec::new(),
+            offline: Vec::new(),
+            online: Vec::new(),
+        }
+    }
+
+    /// Add the changes of another report, which is only
+    /// committed if both are
+    pub fn merge(&mut self, other: Report) {
+        self.committed &= other.committed;
+        self.files.inserted.extend(other.files.inserted);
+        self.files.deleted.extend(other.files.deleted);
+        self.moves.extend(other.moves);
+        self.tags.inserted.extend(other.tags.inserted);
+        self.tags.deleted.extend(other.tags.deleted);
+        self.filetags.inserted.extend(other.filetags.inserted);
+        self.filetags.deleted.extend(other.filetags.deleted);
+        self.skipped.extend(other.skipped);
+        self.offline.extend(other.offline);
+        self.online.extend(other.online);
+    }
+
+    /// Indicates if nothing changed and nothing was skipped
+    pub fn is_empty(&self) -> bool {
+        self.skipped.is_empty() && self.is_unchanged()
+    }
+
+    /// Indicates if nothing changed
+    pub fn is_unchanged(&self) -> bool {
+        self.files.inserted.is_empty() && self.files.deleted.is_empty()
+            && self.moves.is_empty()
+            && self.tags.inserted.is_empty() && self.tags.deleted.is_empty()
+            && self.filetags.inserted.is_empty() && self.filetags.deleted.is_empty()
+            && self.offline.is_empty() && self.online.is_empty()
+    }
+
+    /// Format the report as one change per line
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+        for f in self.files.inserted.iter() { lines.push(format!("INSERT File: {}", f)); }
+        for m in self.moves.iter() { lines.push(format!("MOVE File: {} -> {}", m.from, m.to)); }
+        for f in self.files.deleted.iter() { lines.push(format!("DELETE File: {}", f)); }
+        for f in self.offline.iter() { lines.push(format!("OFFLINE File: {}", f)); }
+        for f in self.online.iter() { lines.push(format!("ONLINE File: {}", f)); }
+        for t in self.tags.inserted.iter() { lines.push(format!("INSERT Tag: {}", t)); }
+        for t in self.tags.deleted.iter() { lines.push(format!("DELETE Tag: {}", t)); }
+        for (f, t) in self.filetags.inserted.iter() { lines.push(format!("INSERT FileTag: {} [{}]", f, t)); }
+        for (f, t) in self.filetags.deleted.iter() { lines.push(format!("DELETE FileTag: {} [{}]", f, t)); }
+        for e in self.skipped.iter() { lines.push(format!("SKIP File: {} ({})", e.path, e.message)); }
+        lines.join("\n")
+    }
+}
+
+/// Stores Insertable values during update. Moved
+/// files are updated in place, keeping their ids.
 pub struct Ins<'u> {
     pub files: Vec<file::Insert<'u>>,
+    pub moves: Vec<file::Move<'u>>,
     pub tags: Vec<tag::Insert<'u>>,
     pub filetags: Vec<FileTag>,
 }
@@ -13,6 +165,7 @@ impl<'u> Ins<'u> {
     pub fn new() -> Self {
         Self {
             files: Vec::new(),
+            moves: Vec::new(),
             tags: Vec::new(),
             filetags: Vec::new(),
         }
@@ -0,0 +1,312 @@
+use super::{import::*, scan::{Rules, Traversal, Walk}, state::Settings};
+use inotify::{Inotify, WatchMask, WatchDescriptor, EventMask};
+use std::{collections::BTreeSet, ffi::OsString, io::ErrorKind};
+use std::{sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
+
+/// How long to sleep when there are no events
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A batch is flushed at the latest after this many
+/// times the delay, even if events keep arriving
+const MAX_DELAY_FACTOR: u32 = 10;
+
+/// The size of the event buffer
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// The events we subscribe to. Changing extended
+/// attributes raises ATTRIB. The self events only
+/// matter for roots, which have no watched parent.
+fn mask() -> WatchMask {
+    WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO | WatchMask::ATTRIB | WatchMask::DONT_FOLLOW
+        | WatchMask::DELETE_SELF | WatchMask::MOVE_SELF
+}
+
+/// Watches directory trees for changes and collects the
+/// affected paths into batches for update::api::run
+pub struct Watch {
+    inotify: Inotify,
+    rules: Rules,
+    roots: Vec<PathBuf>,
+    watches: HashMap<WatchDescriptor, PathBuf>,
+    pending: BTreeSet<PathBuf>,
+    started: Option<Instant>,
+    rescan: bool,
+    delay: Duration,
+}
+
+impl Watch {
+
+    /// Create a new Watch for the given paths. Batches are
+    /// flushed once no event arrived for the given delay.
+    pub fn new(paths: &Vec<&str>, settings: &Settings, delay: Duration) -> Res<Self> {
+        // Directories above the minimum depth still need
+        // watches to learn about their descendants.
+        let traversal = Traversal { min_depth: None, ..settings.traversal.clone() };
+        let mut this = Self {
+            inotify: Inotify::init()?,
+            rules: Rules::new(&settings.ignore, &traversal)?,
+            roots: paths.iter().map(|p| PathBuf::from(p).canonicalize()).collect::<Result<_, _>>()?,
+            watches: HashMap::new(),
+            pending: BTreeSet::new(),
+            started: None,
+            rescan: true,
+            delay: delay,
+        };
+        profile!("watch", { this.watch_roots() });
+        info!("watching {} Directories", this.watches.len());
+        Ok(this)
+    }
+
+    /// Watch all roots. Watching a path again keeps its
+    /// descriptor, so this also refreshes moved paths.
+    fn watch_roots(&mut self) {
+        for root in self.roots.clone() { self.watch_tree(&root) }
+    }
+
+    /// Watch a directory and all of its descendants
+    fn watch_tree(&mut self, root: &Path) {
+        if !root.is_dir() { return self.watch_path(root) }
+        let walk = Walk::with_rules(root.to_string_lossy().into(), &self.rules);
+        for dir in walk.files.iter().filter(|f| f.is_dir()) {
+            self.watch_path(dir);
+        }
+    }
+
+    /// Watch a single path, most likely we ran into the
+    /// limit of user watches if this fails.
+    fn watch_path(&mut self, path: &Path) {
+        match self.inotify.add_watch(path, mask()) {
+            Ok(wd) => { self.watches.insert(wd, path.to_path_buf()); },
+            Err(e) => warn!("failed to watch '{}': {}", path.display(), e),
+        }
+    }
+
+    /// Read and handle all queued events, returning
+    /// whether there were any
+    fn read(&mut self, buffer: &mut [u8]) -> Res<bool> {
+        let events: Vec<(WatchDescriptor, EventMask, Option<OsString>)> =
+            match self.inotify.read_events(buffer) {
+                Ok(events) => events.map(|e| (e.wd, e.mask, e.name.map(|n| n.to_owned()))).collect(),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e.into()),
+            };
+        for (wd, mask, name) in events.iter() {
+            self.handle(wd, *mask, name.as_ref());
+        }
+        Ok(events.len() > 0)
+    }
+
+    /// Add the path of an event to the pending batch
+    fn handle(&mut self, wd: &WatchDescriptor, mask: EventMask, name: Option<&OsString>) {
+        if mask.contains(EventMask::Q_OVERFLOW) {
+            warn!("event queue overflow, rescanning");
+            self.rescan = true;
+            return
+        }
+        let is_root = self.watches.get(wd).map(|p| self.roots.contains(p)).unwrap_or(false);
+        if mask.contains(EventMask::IGNORED) {
+            // A root's watch is dropped once it is deleted or
+            // its volume is unmounted, watch it again if it
+            // is back.
+            self.watches.remove(wd);
+            if is_root { self.rescan = true }
+            return
+        }
+        if is_root && mask.contains(EventMask::MOVE_SELF) {
+            warn!("root '{}' moved, rescanning", self.watches[wd].display());
+            if let Err(e) = self.inotify.rm_watch(wd.clone()) { warn!("failed to remove watch: {}", e) }
+            self.watches.remove(wd);
+            self.rescan = true;
+            return
+        }
+        if mask.contains(EventMask::DELETE_SELF) { return }
+        let is_dir = mask.contains(EventMask::ISDIR);
+        let path = match (self.watches.get(wd), name) {
+            (Some(dir), Some(name)) => dir.join(name),
+            // Directories report changes to themselves, we
+            // already got those from their parent, except
+            // for roots.
+            (Some(path), None) if is_root || !path.is_dir() => path.clone(),
+            _ => return,
+        };
+        if self.rules.is_excluded(&path, is_dir) { return }
+        if self.rules.is_ignore_file(&path) {
+            // The rules of its directory changed, so update all
+            // of it and watch directories which aren't ignored
+            // anymore.
+            if let Some(dir) = path.parent() {
+                self.watch_tree(dir);
+                self.pending.insert(dir.to_path_buf());
+            }
+            return
+        }
+        if is_dir && mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+            self.watch_tree(&path);
+        }
+        self.pending.insert(path);
+    }
+
+    /// Take the pending batch, dropping paths which are
+    /// covered by one of their ancestors
+    fn take_batch(&mut self) -> Vec<String> {
+        if self.rescan {
+            self.rescan = false;
+            self.pending.clear();
+            self.watch_roots();
+            return self.roots.iter().map(|r| r.to_string_lossy().into()).collect()
+        }
+        let mut batch: Vec<PathBuf> = Vec::new();
+        for path in std::mem::take(&mut self.pending) {
+            if batch.last().map(|l| path.starts_with(l)).unwrap_or(false) { continue }
+            batch.push(path);
+        }
+        batch.into_iter().map(|p| p.to_string_lossy().into()).collect()
+    }
+
+    /// Indicates if the pending batch should be flushed
+    fn is_due(&self, quiet: Instant) -> bool {
+        match self.started {
+            _ if self.rescan => true,
+            Some(started) => quiet.elapsed() >= self.delay || started.elapsed() >= self.delay * MAX_DELAY_FACTOR,
+            None => false,
+        }
+    }
+
+    /// Feed the pending batch to the given update
+    /// function. Failed updates are logged, they will be
+    /// retried with the next change.
+    fn flush<F>(&mut self, update: &mut F) where F: FnMut(&Vec<&str>) -> Res<()> {
+        self.started = None;
+        let batch = self.take_batch();
+        if batch.len() == 0 { return }
+        info!("updating {} Path(s)", batch.len());
+        if let Err(e) = update(&batch.iter().map(|p| p.as_str()).collect()) {
+            error!("update failed: {}", e);
+        }
+    }
+
+    /// Catch up with the filesystem, then keep updating until
+    /// stop is set. The pending batch is flushed on stop.
+    pub fn run<F>(&mut self, stop: &AtomicBool, mut update: F) -> Res<()> where F: FnMut(&Vec<&str>) -> Res<()> {
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        let mut quiet = Instant::now();
+        while !stop.load(Ordering::SeqCst) {
+            if self.read(&mut buffer)? {
+                quiet = Instant::now();
+                if self.started.is_none() { self.started = Some(quiet) }
+            } else {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            if self.is_due(quiet) { self.flush(&mut update) }
+        }
+        info!("shutting down");
+        self.flush(&mut update);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+    use super::super::scan::IGNORE_FILE;
+    use crate::{app::attr, util::testing};
+    use std::fs;
+
+    /// Create a directory and watch it
+    fn watch() -> (testing::TempDir, PathBuf, Watch) {
+        let (tmp, dir) = testing::temp_dir();
+        let root = dir.join("root");
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        let mut watch = Watch::new(&vec![root.to_str().unwrap()], &Settings::default(), Duration::from_millis(0)).unwrap();
+        assert_eq!(watch.take_batch(), vec![root.to_string_lossy().to_string()]);
+        (tmp, root, watch)
+    }
+
+    /// Read events until the watch asks for a rescan or
+    /// something is pending
+    fn wait(watch: &mut Watch) {
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        for _ in 0..50 {
+            watch.read(&mut buffer).unwrap();
+            if watch.rescan || watch.pending.len() > 0 { return }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn check_take_batch() {
+        let (_tmp, root, mut watch) = watch();
+        for path in &["a/b/c", "a", "a/b", "ab", "d/e"] {
+            watch.pending.insert(root.join(path));
+        }
+        let expected: Vec<String> = vec!["a", "ab", "d/e"].into_iter().map(|p| root.join(p).to_string_lossy().into()).collect();
+        assert_eq!(watch.take_batch(), expected);
+        assert_eq!(watch.take_batch().len(), 0);
+    }
+
+    #[test]
+    fn check_overflow() {
+        let (_tmp, root, mut watch) = watch();
+        let wd = watch.watches.keys().next().unwrap().clone();
+        watch.pending.insert(root.join("a"));
+        watch.handle(&wd, EventMask::Q_OVERFLOW, None);
+        assert_eq!(watch.is_due(Instant::now()), true);
+        assert_eq!(watch.take_batch(), vec![root.to_string_lossy().to_string()]);
+        assert_eq!(watch.pending.len(), 0);
+        assert_eq!(watch.rescan, false);
+    }
+
+    #[test]
+    fn check_root_events() {
+        let (_tmp, root, mut watch) = watch();
+        let mut file = attr::File::open(root.clone()).unwrap();
+        file.add("foo").unwrap();
+        file.save().unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch(), vec![root.to_string_lossy().to_string()]);
+        fs::remove_dir_all(&root).unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.rescan, true);
+    }
+
+    #[test]
+    fn check_ignore_file() {
+        let (_tmp, root, mut watch) = watch();
+        fs::write(root.join("a").join(IGNORE_FILE), "secret\n").unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch(), vec![root.join("a").to_string_lossy().to_string()]);
+        fs::write(root.join("a/b/secret"), "secret").unwrap();
+        fs::write(root.join("a/b/public"), "public").unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch(), vec![root.join("a/b/public").to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn check_unignored() {
+        let (_tmp, root, mut watch) = watch();
+        fs::write(root.join(IGNORE_FILE), "c/\n").unwrap();
+        wait(&mut watch);
+        watch.take_batch();
+        fs::create_dir(root.join("c")).unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch().len(), 0);
+        fs::remove_file(root.join(IGNORE_FILE)).unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch(), vec![root.to_string_lossy().to_string()]);
+        // The directory is watched now
+        fs::write(root.join("c/d"), "d").unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.take_batch(), vec![root.join("c/d").to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn check_root_moved() {
+        let (_tmp, root, mut watch) = watch();
+        let moved = root.with_file_name("moved");
+        fs::rename(&root, moved).unwrap();
+        wait(&mut watch);
+        assert_eq!(watch.rescan, true);
+        assert!(watch.watches.values().all(|p| p != &root));
+    }
+}
@@ -1,6 +1,7 @@
 use super::{
     import::*,
     config::CommandAction,
+    vocabulary::Vocabulary,
     error::{Error as E}
 };
 use crate::{
@@ -140,8 +141,8 @@ impl Action {
             Self::Tag(TagAction::Link(s)) => { format!("{}({})", names::LINK, s.join(", ")) }
             Self::Tag(TagAction::Unlink(s)) => { format!("{}({})", names::UNLINK, s.join(", ")) }
             Self::Tag(TagAction::Merge(s, d)) => { format!("{}({}, {})", names::MERGE, s, d) }
-            Self::Api(ApiAction::Emit) => { format!("{}", names::EMIT) }
-            Self::Api(ApiAction::Forget) => { format!("{}", names::FORGET) }
+            Self::Api(ApiAction::Emit) => { names::EMIT.to_string() }
+            Self::Api(ApiAction::Forget) => { names::FORGET.to_string() }
             Self::Api(ApiAction::Report(m)) => { format!("{}({})", names::REPORT, m) }
         }
     }
@@ -186,6 +187,14 @@ impl Action {
             }.into())
         }
     }
+    /// Check the tags this action adds against a vocabulary
+    pub fn validate(&self, vocabulary: &Vocabulary) -> Res<()> {
+        match self {
+            Self::Tag(TagAction::Add(tags)) => vocabulary.check(tags.iter().map(|t| t.as_str())),
+            Self::Tag(TagAction::Merge(_, dst)) => vocabulary.check(vec![dst.as_str()]),
+            _ => Ok(()),
+        }
+    }
     pub fn run<'a>(&'a self, results: &'a Results, commit: bool) -> Res<Report<'a>> {
         let mut report = Report::new(self);
         match self {
@@ -225,11 +234,11 @@ impl TagAction {
                 attributes.is_dirty()
             },
             TagAction::Del(tags) => {
-                for t in tags { attributes.del(&t); }
+                for t in tags { attributes.del(t); }
                 attributes.is_dirty()
             },
             TagAction::Merge(src, dst) => {
-                attributes.merge(&src, &dst)?
+                attributes.merge(src, dst)?
             }
         };
         if update {
@@ -23,6 +23,7 @@ pub struct Convention {
 }
 
 pub struct FieldReport {
+    #[allow(dead_code)]
     comment: Option<String>,
     summaries: Vec<Summary>,
 }
@@ -79,7 +80,7 @@ impl Convention {
         if let Some(config_commands) = config.commands {
             commands.extend(
                 config_commands.into_iter()
-                    .filter_map(|c| Command::configure(c, &expand).ok())
+                    .filter_map(|c| Command::configure(c, expand).ok())
             );
         }
 
@@ -99,7 +100,7 @@ impl Convention {
         if let Some(comment) = &self.comment {
             info!(".. {}", comment);
         }
-        let mut report = FieldReport::new(&self);
+        let mut report = FieldReport::new(self);
         for command in &self.commands {
             report.add_summary(command.run(dapi, commit)?);
         }
@@ -108,6 +109,7 @@ impl Convention {
 }
 
 struct Report<'a> {
+    #[allow(dead_code)]
     command: &'a Command,
     reports: Vec<action::Report<'a>>,
 }
@@ -187,8 +189,11 @@ impl Command {
 
     /// Run the Command against the a data interface.
     pub fn run(&self, dapi: &DatabaseLayer, commit: bool) -> Res<Summary> {
+        if let Some(vocabulary) = dapi.vocabulary() {
+            for action in &self.actions { action.validate(vocabulary)?; }
+        }
         let results = dapi.query(&self.pipeline, self.forcings())?;
-        let mut report = Report::new(&self);
+        let mut report = Report::new(self);
         for action in &self.actions {
             let action_report = action.run(&results, commit)?;
             if commit {
@@ -1,16 +1,31 @@
 use crate::{expression::Expansions, util::arg::Options};
-use crate::app::meta::config;
+use crate::app::{meta::{config, vocabulary}, attr::store};
 use super::{import::*, error::{Error as E}};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub dictionary: Option<HashMap<String, String>>,
     pub conventions: Option<Vec<Convention>>,
     pub templates: Option<HashMap<String, Template>>,
+    pub storage: Option<Storage>,
+    pub database: Option<Database>,
+    pub vocabulary: Option<VocabularyConfig>,
+    pub scan: Option<Scan>,
+    pub libraries: Option<Vec<Library>>,
 }
 
 impl Config {
 
+    /// Read the configuration, so the file exists
+    pub fn read_optional(path: &str) -> Res<Self> {
+        if Path::new(path).exists() {
+            Self::read(path)
+        } else {
+            trace!("no configuration at '{}'", path);
+            Ok(Self::default())
+        }
+    }
+
     pub fn read(path: &str) -> Res<Self> {
         use std::io::prelude::*;
         use std::fs::File;
@@ -30,12 +45,14 @@ pub struct Pipeline<'a> {
     pub query: Option<&'a str>,
     pub filter: Option<&'a str>,
     pub pipe: Option<&'a str>,
+    /// Include files on unmounted volumes
+    pub offline: bool,
 }
 
 impl<'a> Pipeline<'a> {
 
     pub fn new() -> Self {
-        Self { query: None, filter: None, pipe: None }
+        Self { query: None, filter: None, pipe: None, offline: false }
     }
 
     pub fn with_query(mut self, query: &'a str) -> Self {
@@ -50,8 +67,12 @@ impl<'a> Pipeline<'a> {
         self.pipe = Some(pipe); self
     }
 
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline; self
+    }
+
     pub fn from_options(o: &'a Options) -> Self {
-        Self { query: o.opt("QUERY"), filter: o.opt("filter"), pipe: o.opt("pipe") }
+        Self { query: o.opt("QUERY"), filter: o.opt("filter"), pipe: o.opt("pipe"), offline: o.flag("include-offline") }
     }
 }
 
@@ -60,12 +81,13 @@ pub struct PipelineBuf {
     pub query: Option<String>,
     pub filter: Option<String>,
     pub pipe: Option<String>,
+    pub offline: bool,
 }
 
 impl PipelineBuf {
 
     pub fn from_config(config: config::Command) -> Self {
-        Self { query: config.query, filter: config.filter, pipe: config.pipe }
+        Self { query: config.query, filter: config.filter, pipe: config.pipe, offline: false }
     }
 
     pub fn from_pipeline(pipeline: &Pipeline) -> Self {
@@ -73,14 +95,16 @@ impl PipelineBuf {
             query: pipeline.query.map(|s| s.to_string()),
             filter: pipeline.filter.map(|s| s.to_string()),
             pipe: pipeline.pipe.map(|s| s.to_string()),
+            offline: pipeline.offline,
         }
     }
 
     pub fn as_pipeline<'a>(&'a self) -> Pipeline<'a> {
         Pipeline {
-            query: self.query.as_ref().map(|s| s.as_str()),
-            filter: self.filter.as_ref().map(|s| s.as_str()),
-            pipe: self.pipe.as_ref().map(|s| s.as_str()),
+            query: self.query.as_deref(),
+            filter: self.filter.as_deref(),
+            pipe: self.pipe.as_deref(),
+            offline: self.offline,
         }
     }
 
@@ -89,6 +113,16 @@ impl PipelineBuf {
         self.filter = self.filter.map(|s| expansions.expand(s)).transpose()?;
         Ok(self)
     }
+
+    /// Restrict the query to root and the paths below it
+    pub fn within(mut self, root: &str) -> Self {
+        let within = format!("([path::{0}] | [path::{0}/%])", root);
+        self.query = Some(match self.query {
+            Some(query) => format!("{} & ({})", within, query),
+            None => within,
+        });
+        self
+    }
 }
 
 
@@ -131,3 +165,50 @@ pub struct Convention {
     pub instances: Option<Vec<Instance>>,
     pub commands: Option<Vec<Command>>,
 }
+
+/// The known tags. Tags from the index are included
+/// if database is set.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VocabularyConfig {
+    pub tags: Option<Vec<String>>,
+    pub database: Option<bool>,
+    pub policy: Option<vocabulary::Policy>,
+}
+
+/// Scan settings. Ignored paths are gitignore-style
+/// patterns, applied in addition to .tagignore files.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Scan {
+    pub ignore: Option<Vec<String>>,
+    pub gitignore: Option<bool>,
+    pub follow_links: Option<bool>,
+    pub one_file_system: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub min_depth: Option<usize>,
+}
+
+/// A named directory tree which belongs to the index.
+/// Its scan settings add to the scan section, its store
+/// takes precedence over the storage section.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Library {
+    pub name: String,
+    pub root: String,
+    pub scan: Option<Scan>,
+    pub storage: Option<store::Kind>,
+}
+
+/// Database settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Database {
+    /// Update the index whenever the tag binary changes tags
+    pub sync: Option<bool>,
+}
+
+/// Selects where tags are stored. Paths below one of the
+/// given roots use the store configured for that root.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Storage {
+    pub default: Option<store::Kind>,
+    pub paths: Option<HashMap<String, store::Kind>>,
+}
@@ -3,6 +3,7 @@ pub mod template;
 pub mod action; // TODO: why pub?
 
 pub mod config;
+pub mod vocabulary;
 
 pub mod prelude {
     pub use super::config::*;
@@ -19,6 +20,7 @@ pub mod export {
     pub use super::command::*;
     pub use super::template::*;
     pub use super::action::*;
+    pub use super::vocabulary::*;
     pub use super::api::*;
 }
 pub use export::*;
@@ -32,17 +34,24 @@ pub mod error {
         ConfigurationError { message: String, },
         #[fail(display = "unknown template: {}", template)]
         UnknownTemplate { template: String, },
+        #[fail(display = "unknown tag: '{}', did you mean: {}", tag, suggestions)]
+        UnknownTag { tag: String, suggestions: String, },
+        #[fail(display = "unknown library: {}", name)]
+        UnknownLibrary { name: String, },
     }
 }
 
 pub mod api {
-    use super::{import::*, template, command};
-    use crate::expression::{Expansions};
+    use super::{import::*, config, template, command, vocabulary::Vocabulary, error::{Error as E}};
+    use crate::{expression::{Expansions}, app::attr::store::{self, Stores}};
 
     pub struct Configuration {
         pub templates: HashMap<String, template::Template>,
         pub conventions: Vec<command::Convention>,
         pub expansions: Expansions,
+        pub vocabulary: Option<Vocabulary>,
+        pub scan: config::Scan,
+        pub libraries: Vec<config::Library>,
     }
 
     impl Configuration {
@@ -69,7 +78,42 @@ pub mod api {
                 for v in c.drain(..) { conventions.push(command::Convention::configure(v, &expansions, &templates)?); }
             }
 
-            Ok(Self { expansions, conventions, templates })
+            // The tag stores are process-wide, since every
+            // attr::File needs to find its store.
+            store::configure(Self::stores(&config)?);
+
+            let vocabulary = config.vocabulary.take().map(|v| Vocabulary::new(
+                v.tags.unwrap_or_default(),
+                v.policy.unwrap_or_default(),
+                v.database.unwrap_or(false),
+            ));
+
+            let scan = config.scan.take().unwrap_or_default();
+            let libraries = config.libraries.take().unwrap_or_default();
+
+            Ok(Self { expansions, conventions, templates, vocabulary, scan, libraries })
+        }
+
+        /// Build the tag store selector from the storage config
+        /// and the libraries
+        fn stores(config: &Config) -> Res<Stores> {
+            let storage = config.storage.clone().unwrap_or_default();
+            let mut stores = Stores::new(storage.default.unwrap_or_default());
+            for (root, kind) in storage.paths.unwrap_or_default() {
+                stores.add_root(Path::new(&root), kind);
+            }
+            for library in config.libraries.iter().flatten() {
+                if let Some(kind) = library.storage {
+                    stores.add_root(Path::new(&library.root), kind);
+                }
+            }
+            Ok(stores)
+        }
+
+        /// Find a library by name
+        pub fn library(&self, name: &str) -> Res<&config::Library> {
+            self.libraries.iter().find(|l| l.name == name)
+                .ok_or_else(|| E::UnknownLibrary { name: name.into() }.into())
         }
 
         pub fn add_template(&mut self, template: template::Template) {
@@ -88,4 +132,25 @@ pub mod api {
             self.expansions.expand(dir)
         }
     }
+
+    #[cfg(test)]
+    mod suite {
+        use super::*;
+
+        #[test]
+        fn check_library_storage() {
+            let config: Config = serde_yaml::from_str(concat!(
+                "storage:\n",
+                "  paths:\n",
+                "    /media/usb: directory\n",
+                "libraries:\n",
+                "  - { name: usb, root: /media/usb, storage: sidecar }\n",
+                "  - { name: music, root: /music }\n",
+            )).unwrap();
+            let stores = Configuration::stores(&config).unwrap();
+            assert_eq!(stores.is_internal(Path::new("/media/usb/.a.tags")), true);
+            assert_eq!(stores.is_internal(Path::new("/media/usb/.tags.1.0.tmp")), false);
+            assert_eq!(stores.is_internal(Path::new("/music/.a.tags")), false);
+        }
+    }
 }
@@ -0,0 +1,131 @@
+use super::{import::*, error::{Error as E}};
+use crate::app::{attr::api, data::DatabaseLayer};
+
+/// The maximum number of suggestions for unknown tags
+const SUGGESTIONS: usize = 3;
+
+/// What happens to tags outside the vocabulary
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[derive(Default)]
+pub enum Policy {
+    #[default]
+    Reject,
+    Warn,
+}
+
+
+
+/// A controlled vocabulary of known tags. Key=value
+/// tags are known if their key is known.
+#[derive(Debug, Clone)]
+pub struct Vocabulary {
+    tags: HashSet<String>,
+    keys: HashSet<String>,
+    policy: Policy,
+    database: bool,
+}
+
+impl Vocabulary {
+
+    /// Create a new vocabulary from the given tags. If
+    /// database is set, the tags in the index are added
+    /// by extend_from_database.
+    pub fn new(tags: Vec<String>, policy: Policy, database: bool) -> Self {
+        let mut this = Self { tags: HashSet::new(), keys: HashSet::new(), policy, database };
+        this.extend(tags);
+        this
+    }
+
+    /// Add tags to the vocabulary
+    pub fn extend(&mut self, tags: impl IntoIterator<Item=String>) {
+        for tag in tags {
+            if let (key, Some(_)) = api::split_value(&tag) {
+                self.keys.insert(key.into());
+            }
+            self.tags.insert(tag);
+        }
+    }
+
+    /// Indicates if the tags in the index belong to
+    /// the vocabulary
+    pub fn needs_database(&self) -> bool {
+        self.database
+    }
+
+    /// Add all tags in the index, so configured
+    pub fn extend_from_database(&mut self, dapi: &DatabaseLayer) -> Res<()> {
+        if self.database {
+            self.extend(dapi.query_all_tags()?.into_iter().map(|t| t.1));
+        }
+        Ok(())
+    }
+
+    /// Check if the vocabulary contains a tag
+    pub fn contains(&self, tag: &str) -> bool {
+        self.tags.contains(tag) || match api::split_value(tag) {
+            (key, Some(_)) => self.keys.contains(key) || self.tags.contains(key),
+            (_, None) => false,
+        }
+    }
+
+    /// Return the known tags closest to the given tag
+    pub fn suggest<'v>(&'v self, tag: &str) -> Vec<&'v str> {
+        let limit = std::cmp::max(2, tag.chars().count() / 3);
+        let mut close: Vec<(usize, &str)> = self.tags.iter()
+            .map(|t| (util::string::edit_distance(tag, t), t.as_str()))
+            .filter(|(d, _)| *d <= limit)
+            .collect();
+        close.sort();
+        close.into_iter().take(SUGGESTIONS).map(|(_, t)| t).collect()
+    }
+
+    /// Check that all tags are known. Depending on the
+    /// policy, unknown tags are rejected or warned about.
+    pub fn check<'t>(&self, tags: impl IntoIterator<Item=&'t str>) -> Res<()> {
+        for tag in tags {
+            if self.contains(tag) { continue }
+            let suggestions = self.suggest(tag).join(", ");
+            match self.policy {
+                Policy::Reject => {
+                    return Err(E::UnknownTag { tag: tag.into(), suggestions }.into())
+                }
+                Policy::Warn => {
+                    warn!("unknown tag: '{}', did you mean: {}", tag, suggestions);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+
+    fn vocabulary() -> Vocabulary {
+        let tags = vec!["Lasagna", "Lasagne", "Pizza", "Rating=4"];
+        Vocabulary::new(tags.into_iter().map(String::from).collect(), Policy::Reject, false)
+    }
+
+    #[test]
+    fn check_contains() {
+        let v = vocabulary();
+        assert_eq!(v.contains("Pizza"), true);
+        assert_eq!(v.contains("Rating=5"), true);
+        assert_eq!(v.contains("Pasta"), false);
+    }
+
+    #[test]
+    fn check_suggest() {
+        let v = vocabulary();
+        assert_eq!(v.suggest("Lasgna"), vec!["Lasagna", "Lasagne"]);
+        assert_eq!(v.suggest("Muffin").len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "UnknownTag")]
+    fn check_reject() {
+        vocabulary().check(vec!["Lasgna"]).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 pub mod attr;
 pub mod data;
 pub mod meta;
+pub mod archive;
 
 pub mod import {
     pub use super::super::import::*;
@@ -1,19 +1,80 @@
 #![feature(test)]
+#![allow(clippy::redundant_static_lifetimes, clippy::redundant_field_names, clippy::needless_lifetimes, clippy::len_zero)]
+#![allow(clippy::new_without_default, clippy::len_without_is_empty, clippy::ptr_arg, clippy::type_complexity, clippy::module_inception)]
+#![allow(clippy::bool_assert_comparison, clippy::self_named_constructors, clippy::inherent_to_string)]
 #[macro_use] extern crate log;
+#[macro_use] extern crate failure;
 extern crate env_logger;
 extern crate clap;
+extern crate glob;
+extern crate regex;
+extern crate serde_json;
+extern crate serde_yaml;
 extern crate tag_suite;
 
-pub use tag_suite::{import::*, app::attr::{api, Tag, File}, util::arg::{Options}};
+pub use tag_suite::{import::*, app::attr::{api, Tag, File, TAG_SEPERATOR}, util::arg::{Options}};
+pub use tag_suite::{app::meta::{Configuration, Vocabulary, config::{Config as AppConfig}}, defaults};
+pub use tag_suite::app::{attr::store, data::{DatabaseLayer, update::Walk, query::collect::{SerialFile, SerialFileBuf}}};
+pub use tag_suite::db::export::*;
 pub use clap::{App, ArgMatches, Arg, SubCommand};
+pub use regex::Regex;
+
+pub mod error {
+    #[derive(Debug, Fail)]
+    pub enum Error {
+        #[fail(display = "edit aborted: {}", message)]
+        EditAborted { message: String, },
+    }
+}
+use error::{Error as E};
+
+/// The header of documents opened by 'tag edit'
+const EDIT_HEADER: &'static str = "\
+# Edit the tags of the files below, then save and quit.
+# Lines starting with '#' are ignored.
+";
+
+/// The tags to add to and remove from a file
+type Change = (usize, Vec<String>, Vec<String>);
+
+/// Output formats for the files we operated on
+#[derive(Debug, Copy, Clone)]
+enum Format {
+    Json,
+    Yaml,
+    Ndjson,
+    Plain,
+    Line,
+}
+
+impl Format {
+    fn parse(format: &str) -> Self {
+        match format {
+            "json" => Format::Json,
+            "yaml" => Format::Yaml,
+            "ndjson" => Format::Ndjson,
+            "plain" => Format::Plain,
+            _ => Format::Line,
+        }
+    }
+
+    /// Whether each file is written on a line of its own
+    fn is_line(self) -> bool {
+        matches!(self, Format::Ndjson | Format::Plain | Format::Line)
+    }
+}
 
 #[derive(Debug)]
 enum Command<'a> {
     Add(&'a str),
     Del(&'a str),
     Merge(&'a str, &'a str),
+    Migrate(&'a str),
     Purge,
     Get,
+    Edit(bool),
+    Copy(&'a str, bool, Vec<Regex>),
+    Sync(Vec<Regex>),
     Nop,
 }
 
@@ -21,42 +82,382 @@ struct Cli {}
 
 impl Cli {
 
-    pub fn add(file: &Path, tag: &str) -> Res<()> {
+    pub fn add(file: &Path, tag: &str) -> Res<File> {
         let mut f = File::open(file.into())?;
         f.add(tag)?; f.save()?;
-        Ok(())
+        Ok(f)
     }
 
-    pub fn del(file: &Path, tag: &str) -> Res<()> {
+    pub fn del(file: &Path, tag: &str) -> Res<File> {
         let mut f = File::open(file.into())?;
         f.del(tag); f.save()?;
-        Ok(())
+        Ok(f)
     }
 
-    pub fn get(file: &Path) -> Res<()> {
-        let f = File::open(file.into())?;
-        use std::io::{self, Write};
-        match write!(io::stdout(), "{}\n", f.format()) {
-            Err(e) => {
-                Err(e.into())
-            },
-            _ => Ok(())
-        }
+    pub fn get(file: &Path) -> Res<File> {
+        File::open(file.into())
     }
 
-    pub fn purge(file: &Path) -> Res<()> {
+    pub fn purge(file: &Path) -> Res<File> {
         let mut f = File::open(file.into())?;
         f.purge(); f.save()?;
-        Ok(())
+        Ok(f)
     }
 
-    pub fn merge(file: &Path, src: &str, dst: &str) -> Res<()> {
+    pub fn merge(file: &Path, src: &str, dst: &str) -> Res<File> {
         let mut f = File::open(file.into())?;
         f.merge(src, dst)?; f.save()?;
+        Ok(f)
+    }
+
+    pub fn migrate(file: &Path, sep: &str) -> Res<File> {
+        if api::migrate(file, sep)? {
+            info!("migrated: {}", file.to_string_lossy());
+        }
+        File::open(file.into())
+    }
+
+    /// Let the user edit the tags of the files in $EDITOR.
+    /// The tags added are passed to check before anything
+    /// is applied.
+    pub fn edit(mut files: Vec<File>, yes: bool, check: &dyn Fn(Vec<&str>) -> Res<()>) -> Res<Vec<File>> {
+        use std::{fs, process};
+        // Fail before any editing if we can't ask
+        let tty = match yes {
+            true => None,
+            false => Some(Self::terminal().map_err(|e| E::EditAborted {
+                message: format!("can't ask for confirmation without a terminal ({}), use --yes", e)
+            })?),
+        };
+        let original: Vec<SerialFileBuf> = files.iter()
+            .map(|f| SerialFile::from_file(f).into())
+            .collect();
+        let document = format!("{}{}\n", EDIT_HEADER, serde_yaml::to_string(&original)?);
+        let path = Self::create_temp(document.as_bytes())?;
+
+        let editor = std::env::var("VISUAL")
+            .or(std::env::var("EDITOR"))
+            .unwrap_or("vi".into());
+        // The file list may have been read from stdin
+        let stdin = match Self::terminal() {
+            Ok(tty) => process::Stdio::from(tty),
+            Err(_) => process::Stdio::inherit(),
+        };
+        let status = process::Command::new("sh")
+            .arg("-c").arg(format!("{} \"$1\"", editor))
+            .arg("sh").arg(&path)
+            .stdin(stdin)
+            .status();
+        match status {
+            Ok(ref status) if status.success() => {},
+            _ => fs::remove_file(&path)?,
+        }
+        let status = status?;
+        if !status.success() {
+            return Err(E::EditAborted { message: format!("'{}' failed with {}", editor, status) }.into())
+        }
+
+        let kept = |e: Error| -> Error { E::EditAborted {
+            message: format!("{}, your changes are kept in {}", e, path.to_string_lossy())
+        }.into() };
+        let edited: Vec<SerialFileBuf> = serde_yaml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| kept(e.into()))?;
+        let changes = Self::diff(&files, edited).map_err(kept)?;
+        let added: Vec<&str> = changes.iter()
+            .flat_map(|(_, add, _)| add.iter().map(|t| t.as_str()))
+            .collect();
+        if added.len() > 0 { check(added).map_err(kept)?; }
+        fs::remove_file(&path)?;
+
+        if changes.len() == 0 {
+            eprintln!("no changes");
+            return Ok(Vec::new())
+        }
+        for (n, add, del) in changes.iter() {
+            eprintln!("{}", files[*n].path_str());
+            for tag in add.iter() { eprintln!(" + {}", tag); }
+            for tag in del.iter() { eprintln!(" - {}", tag); }
+        }
+        if let Some(tty) = tty {
+            if !Self::confirm(tty, &format!("apply changes to {} file(s)?", changes.len()))? {
+                return Err(E::EditAborted { message: "not confirmed".into() }.into())
+            }
+        }
+
+        let mut changed = Vec::new();
+        for (n, add, del) in changes {
+            let file = &mut files[n];
+            for tag in del.iter() { file.del(tag); }
+            for tag in add.iter() { file.add(tag)?; }
+            file.save()?;
+            changed.push(n);
+        }
+        Ok(files.into_iter().enumerate()
+            .filter(|(n, _)| changed.contains(n))
+            .map(|(_, f)| f)
+            .collect())
+    }
+
+    /// Create a new temporary file only we can access. We
+    /// never open an existing file, which someone else
+    /// may have placed there.
+    fn create_temp(contents: &[u8]) -> Res<PathBuf> {
+        use std::{fs::OpenOptions, io::{ErrorKind, Write}, os::unix::fs::OpenOptionsExt, process};
+        let mut n = 0;
+        loop {
+            let path = std::env::temp_dir().join(format!("tag-edit-{}-{}.yaml", process::id(), n));
+            match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(contents)?;
+                    return Ok(path)
+                },
+                Err(ref e) if e.kind() == ErrorKind::AlreadyExists && n < 100 => n += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Compare the edited tag sets to the files. Unknown
+    /// paths or invalid tags abort the edit.
+    fn diff(files: &Vec<File>, edited: Vec<SerialFileBuf>) -> Res<Vec<Change>> {
+        let index: HashMap<&str, usize> = files.iter().enumerate()
+            .map(|(n, f)| (f.path_str(), n))
+            .collect();
+        let mut seen = HashSet::new();
+        let mut changes = Vec::new();
+        for entry in edited {
+            let n = match index.get(entry.path.as_str()) {
+                Some(n) => *n,
+                None => return Err(E::EditAborted { message: format!("unknown path: {}", entry.path) }.into()),
+            };
+            if !seen.insert(n) {
+                return Err(E::EditAborted { message: format!("duplicate path: {}", entry.path) }.into())
+            }
+            for tag in entry.tags.iter() { api::sanitize(tag)?; }
+            let before: HashSet<&str> = files[n].iter()
+                .map(|t| t.as_str())
+                .filter(|t| api::ghostbuster(t).is_some())
+                .collect();
+            let after: HashSet<&str> = entry.tags.iter().map(|t| t.as_str()).collect();
+            let mut add: Vec<String> = after.difference(&before).map(|t| t.to_string()).collect();
+            let mut del: Vec<String> = before.difference(&after).map(|t| t.to_string()).collect();
+            if add.len() + del.len() > 0 {
+                add.sort(); del.sort();
+                changes.push((n, add, del));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Open the controlling terminal for reading
+    fn terminal() -> std::io::Result<std::fs::File> {
+        std::fs::File::open("/dev/tty")
+    }
+
+    /// Ask the user for confirmation on the terminal, as
+    /// stdin may have been used for the file list
+    fn confirm(tty: std::fs::File, question: &str) -> Res<bool> {
+        use std::io::{self, BufRead, BufReader, Write};
+        eprint!("{} [y/N] ", question);
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        BufReader::new(tty).read_line(&mut answer)?;
+        Ok(answer.trim().eq_ignore_ascii_case("y"))
+    }
+
+    /// Indicates if a tag matches any of the filters, or
+    /// if there are no filters
+    fn matches(tag: &Tag, only: &Vec<Regex>) -> bool {
+        only.len() == 0 || only.iter().any(|r| r.is_match(tag.as_str()))
+    }
+
+    /// Copy the matching tags of src onto a file. Unless
+    /// we create a union, the files matching tags are
+    /// replaced.
+    pub fn cp(src: &File, file: &Path, union: bool, only: &Vec<Regex>) -> Res<File> {
+        let mut f = File::open(file.into())?;
+        let mut tags: HashSet<Tag> = f.tags().iter()
+            .filter(|t| union || !Self::matches(t, only))
+            .cloned()
+            .collect();
+        tags.extend(src.tags().iter().filter(|t| Self::matches(t, only)).cloned());
+        if &tags != f.tags() {
+            f.set_tags(&tags); f.save()?;
+        }
+        Ok(f)
+    }
+
+    /// Give all files the union of their matching tags
+    pub fn sync(mut files: Vec<File>, only: &Vec<Regex>) -> Res<Vec<File>> {
+        let union: HashSet<Tag> = files.iter()
+            .flat_map(|f| f.tags().iter())
+            .filter(|t| Self::matches(t, only))
+            .cloned()
+            .collect();
+        for f in files.iter_mut() {
+            let mut tags = f.tags().clone();
+            tags.extend(union.iter().cloned());
+            if &tags != f.tags() {
+                f.set_tags(&tags); f.save()?;
+            }
+        }
+        Ok(files)
+    }
+
+    /// Check tags against the vocabulary, including the
+    /// tags in the index if configured
+    pub fn check_vocabulary(mut vocabulary: Vocabulary, database: &str, tags: Vec<&str>) -> Res<()> {
+        if vocabulary.needs_database() {
+            let pool = db::Connection::new_pool(database, 1)?;
+            let dapi = DatabaseLayer::new(db::Connection(pool.get().expect("database connection failure")));
+            vocabulary.extend_from_database(&dapi)?;
+        }
+        vocabulary.check(tags)
+    }
+
+    /// Update the index for the files we touched
+    pub fn update_index(database: &str, files: &Vec<File>) -> Res<()> {
+        let pool = db::Connection::new_pool(database, 1)?;
+        let dapi = DatabaseLayer::new(db::Connection(pool.get().expect("database connection failure")));
+        dapi.update(&files.iter().map(|f| f.path_str()).collect())?;
+        Ok(())
+    }
+
+    /// Write the files in the given format
+    pub fn output(files: &Vec<File>, format: Format) -> Res<()> {
+        let serial = || files.iter().map(SerialFile::from_file).collect::<Vec<_>>();
+        let output = match format {
+            Format::Json => format!("{}\n", serde_json::to_string(&serial())?),
+            Format::Yaml => format!("{}\n", serde_yaml::to_string(&serial())?),
+            _ => files.iter()
+                .map(|f| Self::line(f, format))
+                .collect::<Res<String>>()?,
+        };
+        Self::write(&output)
+    }
+
+    /// Format a single file in one of the line formats
+    pub fn line(file: &File, format: Format) -> Res<String> {
+        Ok(match format {
+            Format::Ndjson => serde_json::to_string(&SerialFile::from_file(file))? + "\n",
+            Format::Plain => SerialFile::from_file(file).to_plain() + "\n",
+            _ => file.format() + "\n",
+        })
+    }
+
+    /// Write to stdout, a closed pipe is fine
+    pub fn write(output: &str) -> Res<()> {
+        use std::io::{self, Write};
+        match write!(io::stdout(), "{}", output) {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            r => Ok(r?),
+        }
+    }
+
+    /// Report failed files as JSON on stderr
+    pub fn report(errors: &Vec<(PathBuf, Error)>) -> Res<()> {
+        let report: Vec<serde_json::Value> = errors.iter()
+            .map(|(file, error)| serde_json::json!({
+                "path": file.to_string_lossy(),
+                "error": error.to_string(),
+            }))
+            .collect();
+        eprintln!("{}", serde_json::to_string(&serde_json::json!({ "errors": report }))?);
         Ok(())
     }
 }
 
+/// Arguments for reading the files to operate on
+/// from stdin and recursing into directories
+fn input_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("stdin")
+            .help("Read the file(s) from stdin, one per line")
+            .long("stdin"),
+        Arg::with_name("null")
+            .help("Input file names are terminated by NUL instead of newline")
+            .short("0")
+            .long("null")
+            .requires("stdin"),
+        Arg::with_name("recursive")
+            .help("Operate on all files below the given directories")
+            .short("R")
+            .long("recursive"),
+    ]
+}
+
+/// Read file names from stdin. Names are taken as raw
+/// bytes, so any file name can be passed with --null.
+fn read_stdin(null: bool) -> Res<Vec<PathBuf>> {
+    use std::{ffi::OsStr, io::{self, Read}, os::unix::ffi::OsStrExt};
+    let mut buffer = Vec::new();
+    io::stdin().read_to_end(&mut buffer)?;
+    let delimiter = if null { b'\0' } else { b'\n' };
+    Ok(buffer.split(|b| *b == delimiter)
+        .filter(|s| s.len() > 0)
+        .map(|s| PathBuf::from(OsStr::from_bytes(s)))
+        .collect())
+}
+
+/// Expand glob patterns, so they don't name an
+/// existing file, and directories, if recursive
+fn expand(path: PathBuf, recursive: bool, files: &mut Vec<PathBuf>) -> Res<()> {
+    let pattern = path.to_str()
+        .filter(|p| !path.exists() && p.contains(|c: char| c == '*' || c == '?' || c == '['))
+        .map(String::from);
+    let paths = match pattern {
+        Some(pattern) => glob::glob(&pattern)?.filter_map(|p| p.ok()).collect(),
+        None => vec![path],
+    };
+    for path in paths {
+        if recursive && path.is_dir() {
+            let walk = Walk::new(path.to_string_lossy().into());
+            files.extend(walk.files.into_iter()
+                .filter(|p| !p.is_dir())
+                .filter(|p| !store::is_internal(p)));
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Compile the --only filters
+fn filters(options: &ArgMatches) -> Res<Vec<Regex>> {
+    Options::_opt_vec(options, "only")
+        .unwrap_or_default().into_iter()
+        .map(|pattern| Ok(Regex::new(&util::string::like_to_regex(pattern))?))
+        .collect()
+}
+
+/// An argument filtering the tags to copy
+fn only_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("only")
+        .help("Only copy tags matching PATTERN, where % matches anything, e.g. 'Artist::%'. Copied tags already exist, so they aren't checked against the vocabulary")
+        .long("only")
+        .value_name("PATTERN")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+}
+
+/// Collect the files to operate on from the arguments
+/// and stdin
+fn collect_files(options: &ArgMatches) -> Res<Vec<PathBuf>> {
+    let mut args: Vec<PathBuf> = Options::_opt_vec(options, "FILE")
+        .unwrap_or_default().into_iter()
+        .map(PathBuf::from)
+        .collect();
+    if options.is_present("stdin") {
+        args.extend(read_stdin(options.is_present("null"))?);
+    }
+    let mut files = Vec::new();
+    for arg in args.into_iter() {
+        expand(arg, options.is_present("recursive"), &mut files)?;
+    }
+    Ok(files)
+}
+
 fn main() -> Res<()> {
 
     env_logger::init();
@@ -69,23 +470,29 @@ fn main() -> Res<()> {
 
         .subcommand(SubCommand::with_name("add")
             .about("Add a tag to files")
+            .arg(Arg::with_name("new")
+                .help("Allow a tag outside the vocabulary")
+                .short("n")
+                .long("new"))
             .arg(Arg::with_name("TAG")
                 .help("The tag to add")
                 .required(true)
                 .takes_value(true))
             .arg(Arg::with_name("FILE")
                 .help("The file(s) to tag")
-                .required(true)
+                .required_unless("stdin")
                 .takes_value(true)
-                .multiple(true)))
+                .multiple(true))
+            .args(&input_args()))
 
         .subcommand(SubCommand::with_name("get")
             .about("Get tag data from files")
             .arg(Arg::with_name("FILE")
                 .help("The file(s) to query")
-                .required(true)
+                .required_unless("stdin")
                 .takes_value(true)
-                .multiple(true)))
+                .multiple(true))
+            .args(&input_args()))
 
         .subcommand(SubCommand::with_name("del")
             .about("Remove a tag from files")
@@ -95,20 +502,26 @@ fn main() -> Res<()> {
                 .takes_value(true))
             .arg(Arg::with_name("FILE")
                 .help("The file(s) to untag")
-                .required(true)
+                .required_unless("stdin")
                 .takes_value(true)
-                .multiple(true)))
+                .multiple(true))
+            .args(&input_args()))
 
         .subcommand(SubCommand::with_name("purge")
             .about("Purge all tags from the given files")
             .arg(Arg::with_name("FILE")
                 .help("The file(s) to query")
-                .required(true)
+                .required_unless("stdin")
                 .takes_value(true)
-                .multiple(true)))
+                .multiple(true))
+            .args(&input_args()))
 
         .subcommand(SubCommand::with_name("merge")
             .about("Merge the the source tag into the destination - equivalent to renaming a tag")
+            .arg(Arg::with_name("new")
+                .help("Allow a destination tag outside the vocabulary")
+                .short("n")
+                .long("new"))
             .arg(Arg::with_name("SRC")
                 .help("The tag to merge")
                 .required(true)
@@ -119,70 +532,246 @@ fn main() -> Res<()> {
                 .takes_value(true))
             .arg(Arg::with_name("FILE")
                 .help("The file(s) to operate on")
+                .required_unless("stdin")
+                .takes_value(true)
+                .multiple(true))
+            .args(&input_args()))
+
+        .subcommand(SubCommand::with_name("cp")
+            .about("Copy the tags of the source to other files, replacing their tags")
+            .arg(Arg::with_name("union")
+                .help("Add the tags instead of replacing them")
+                .short("u")
+                .long("union"))
+            .arg(only_arg())
+            .arg(Arg::with_name("SRC")
+                .help("The file to copy tags from")
                 .required(true)
+                .takes_value(true))
+            .arg(Arg::with_name("FILE")
+                .help("The file(s) to copy tags to")
+                .required_unless("stdin")
                 .takes_value(true)
-                .multiple(true)))
+                .multiple(true))
+            .args(&input_args()))
+
+        .subcommand(SubCommand::with_name("sync")
+            .about("Give all files the union of their tags")
+            .arg(only_arg())
+            .arg(Arg::with_name("FILE")
+                .help("The files to sync")
+                .required_unless("stdin")
+                .takes_value(true)
+                .multiple(true))
+            .args(&input_args()))
+
+        .subcommand(SubCommand::with_name("edit")
+            .about("Edit the tags of files in $EDITOR")
+            .arg(Arg::with_name("new")
+                .help("Allow added tags outside the vocabulary")
+                .short("n")
+                .long("new"))
+            .arg(Arg::with_name("yes")
+                .help("Apply the changes without asking")
+                .short("y")
+                .long("yes"))
+            .arg(Arg::with_name("FILE")
+                .help("The file(s) to edit")
+                .required_unless("stdin")
+                .takes_value(true)
+                .multiple(true))
+            .args(&input_args()))
+
+        .subcommand(SubCommand::with_name("migrate")
+            .about("Rewrite legacy tag data in the current encoding")
+            .arg(Arg::with_name("seperator")
+                .help("The seperator used by the legacy tag data")
+                .long("seperator")
+                .short("s")
+                .default_value(TAG_SEPERATOR)
+                .takes_value(true))
+            .arg(Arg::with_name("FILE")
+                .help("The file(s) to migrate")
+                .required_unless("stdin")
+                .takes_value(true)
+                .multiple(true))
+            .args(&input_args()))
+
+        .arg(Arg::with_name("format")
+            .help("Print the resulting files as json, yaml, ndjson or plain")
+            .long("format")
+            .short("F")
+            .global(true)
+            .possible_values(&["json", "yaml", "ndjson", "plain"])
+            .takes_value(true))
+
+        .arg(Arg::with_name("sync")
+            .help("Update the index for the changed files")
+            .long("sync")
+            .global(true)
+            .conflicts_with("no-sync"))
+
+        .arg(Arg::with_name("no-sync")
+            .help("Don't update the index, even if configured")
+            .long("no-sync")
+            .global(true))
+
+        .arg(Arg::with_name("database")
+            .help("Use the given sqlite database FILE")
+            .long("database")
+            .short("d")
+            .global(true)
+            .value_name("FILE")
+            .takes_value(true))
 
         .get_matches();
 
     let (files, command) = {
         if let Some(options) = args.subcommand_matches("add") {
-            (Options::_vec(options, "FILE"), Command::Add(Options::_get(options, "TAG")))
+            (collect_files(options)?, Command::Add(Options::_get(options, "TAG")))
         } else if let Some(options) = args.subcommand_matches("del") {
-            (Options::_vec(options, "FILE"), Command::Del(Options::_get(options, "TAG")))
+            (collect_files(options)?, Command::Del(Options::_get(options, "TAG")))
         } else if let Some(options) = args.subcommand_matches("get") {
-            (Options::_vec(options, "FILE"), Command::Get)
+            (collect_files(options)?, Command::Get)
         } else if let Some(options) = args.subcommand_matches("purge") {
-            (Options::_vec(options, "FILE"), Command::Purge)
+            (collect_files(options)?, Command::Purge)
         } else if let Some(options) = args.subcommand_matches("merge") {
-            (Options::_vec(options, "FILE"), Command::Merge(Options::_get(options, "SRC"), Options::_get(options, "DST")))
+            (collect_files(options)?, Command::Merge(Options::_get(options, "SRC"), Options::_get(options, "DST")))
+        } else if let Some(options) = args.subcommand_matches("edit") {
+            (collect_files(options)?, Command::Edit(options.is_present("yes")))
+        } else if let Some(options) = args.subcommand_matches("cp") {
+            (collect_files(options)?, Command::Copy(Options::_get(options, "SRC"), options.is_present("union"), filters(options)?))
+        } else if let Some(options) = args.subcommand_matches("sync") {
+            (collect_files(options)?, Command::Sync(filters(options)?))
+        } else if let Some(options) = args.subcommand_matches("migrate") {
+            (collect_files(options)?, Command::Migrate(Options::_get(options, "seperator")))
         } else { (Vec::new(), Command::Nop) }
     };
     trace!("command: {:?}", command);
 
+    trace!("reading configuration");
+    let config = AppConfig::read_optional(&defaults::config_path(defaults::CONFIG_NAME))?;
+    // Global options may appear before or after the subcommand
+    let sub = args.subcommand().1;
+    let present = |key: &str| args.is_present(key) || sub.map(|o| o.is_present(key)).unwrap_or(false);
+    let value = |key: &str| sub.and_then(|o| o.value_of(key)).or(args.value_of(key));
+    let sync = {
+        let configured = config.database.as_ref().and_then(|d| d.sync).unwrap_or(false);
+        (configured || present("sync")) && !present("no-sync")
+    };
+    let conf = Configuration::configure(config)?;
+    let database = value("database").map(|s| s.to_string())
+        .unwrap_or(defaults::data_path(defaults::DATABASE_PATH));
+
+    let vocabulary = conf.vocabulary.filter(|_| !present("new"));
+    let check = |tags: Vec<&str>| match vocabulary {
+        Some(ref vocabulary) => Cli::check_vocabulary(vocabulary.clone(), &database, tags),
+        None => Ok(()),
+    };
+    match command {
+        Command::Add(tag) => check(vec![tag])?,
+        Command::Merge(_, dst) => check(vec![dst])?,
+        _ => {},
+    }
+
+    // Only 'get' prints files by default
+    let format = match command {
+        Command::Get => Some(value("format").map_or(Format::Line, Format::parse)),
+        _ => value("format").map(Format::parse),
+    };
+
+    let total = files.len();
+    let mut done = Vec::new();
     let mut errors = Vec::new();
     let mut err = |f: &Path, r| {
         match r {
+            Ok(file) => done.push(file),
             Err(e) => errors.push((f.to_owned(), e)),
-            _ => {},
         }
     };
 
     trace!("running command..");
-    let files = files.iter().map(|f| Path::new(f));
+    let files = files.iter().map(|f| f.as_path());
     match command {
         Command::Del(tag) => {
             trace!("deleting '{}' from {} files", tag, files.len());
-            for file in files { err(&file, Cli::del(file, tag)); }
+            for file in files { err(file, Cli::del(file, tag)); }
         }
         Command::Add(tag) => {
             trace!("adding '{}' to {} files", tag, files.len());
-            for file in files { err(&file, Cli::add(file, tag)); }
+            for file in files { err(file, Cli::add(file, tag)); }
         }
         Command::Get => {
             trace!("querying {} files", files.len());
-            for file in files { err(&file, Cli::get(file)); }
+            // Line formats print each file as soon as it is opened
+            let stream = format.filter(|f| f.is_line());
+            for file in files {
+                match (Cli::get(file), stream) {
+                    (Ok(opened), Some(format)) => Cli::write(&Cli::line(&opened, format)?)?,
+                    (r, _) => err(file, r),
+                }
+            }
         }
         Command::Merge(src, dst) => {
             trace!("merging '{}' into '{}' in {} files", src, dst, files.len());
-            for file in files { err(&file, Cli::merge(file, src, dst)); }
+            for file in files { err(file, Cli::merge(file, src, dst)); }
+        }
+        Command::Migrate(sep) => {
+            trace!("migrating {} files", files.len());
+            for file in files { err(file, Cli::migrate(file, sep)); }
         }
         Command::Purge => {
             trace!("purging {} files", files.len());
-            for file in files { err(&file, Cli::purge(file)); }
+            for file in files { err(file, Cli::purge(file)); }
+        }
+        Command::Copy(src, union, ref only) => {
+            trace!("copying tags of '{}' to {} files", src, files.len());
+            let src = File::open(src.into())?;
+            for file in files { err(file, Cli::cp(&src, file, union, only)); }
+        }
+        Command::Sync(ref only) => {
+            trace!("syncing {} files", files.len());
+            for file in files { err(file, Cli::get(file)); }
+            let opened = std::mem::take(&mut done);
+            done = Cli::sync(opened, only)?;
+        }
+        Command::Edit(yes) => {
+            trace!("editing {} files", files.len());
+            for file in files { err(file, Cli::get(file)); }
+            let opened = std::mem::take(&mut done);
+            done = Cli::edit(opened, yes, &check)?;
         }
         Command::Nop => { }
     }
 
-    if errors.len() > 0 {
-        error!("{} error(s) occurred:", errors.len());
+    // The files were changed either way, so a failed
+    // index update is reported after the files
+    let modified = !matches!(command, Command::Get | Command::Nop);
+    let indexed = if sync && modified && done.len() > 0 {
+        trace!("updating {} files in {}", done.len(), database);
+        Cli::update_index(&database, &done)
+    } else {
+        Ok(())
+    };
+
+    if let Some(format) = format {
+        Cli::output(&done, format)?;
     }
 
-    for (file, error) in errors {
-        error!("{}: {}", error, file.to_string_lossy());
+    if errors.len() > 0 {
+        error!("{} error(s) occurred:", errors.len());
+        for (file, error) in errors.iter() {
+            error!("{}: {}", error, file.to_string_lossy());
+        }
+        Cli::report(&errors)?;
+        if let Err(e) = indexed {
+            error!("failed to update the index: {}", e);
+        }
+        // 1: some files failed, 2: all files failed
+        std::process::exit(if errors.len() < total { 1 } else { 2 });
     }
 
-    Ok(())
+    indexed
 }
 
 #[cfg(test)]
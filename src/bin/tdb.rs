@@ -1,4 +1,7 @@
 #![feature(test, box_syntax)]
+#![allow(clippy::redundant_static_lifetimes, clippy::redundant_field_names, clippy::needless_lifetimes, clippy::len_zero)]
+#![allow(clippy::new_without_default, clippy::len_without_is_empty, clippy::ptr_arg, clippy::type_complexity, clippy::module_inception)]
+#![allow(clippy::bool_assert_comparison, clippy::self_named_constructors, clippy::inherent_to_string)]
 #[macro_use] extern crate log;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate tag_suite;
@@ -6,13 +9,17 @@ extern crate test;
 extern crate env_logger;
 extern crate clap;
 extern crate owning_ref;
+extern crate ctrlc;
+extern crate serde_json;
 
 use clap::{App, Arg, SubCommand, ArgMatches};
 use tag_suite::{import::*, db::export::*, util::{arg::Options}};
-use tag_suite::app::data::{DatabaseLayer, query::{self, collect}};
+use tag_suite::app::data::{DatabaseLayer, update, query::{self, collect}};
 use tag_suite::app::meta::{Configuration, config};
+use tag_suite::app::archive::{Manifest, Restore, Located};
 use tag_suite::util::profiler;
 use std::io::{self, Write};
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, time::Duration};
 use collect::Stringify;
 use tag_suite::defaults;
 
@@ -29,7 +36,7 @@ use error::{Error as E};
 #[derive(Debug, Clone)]
 pub enum QueryCommand<'a> {
     Output(config::Pipeline<'a>),
-    Map(config::Pipeline<'a>, config::CommandAction<'a>, bool),
+    Map(config::Pipeline<'a>, config::CommandAction<'a>, bool, bool),
     Count(config::Pipeline<'a>),
     Serialize(config::Pipeline<'a>, Option<&'a str>),
 }
@@ -41,6 +48,22 @@ pub enum TagCommand<'a> {
     Statistics(Option<&'a str>),
 }
 
+#[derive(Debug, Clone)]
+pub enum ArchiveCommand<'a> {
+    Export(config::Pipeline<'a>, Option<&'a str>, Option<&'a str>, Option<&'a str>),
+    Import(&'a str, Option<&'a str>, bool),
+}
+
+#[derive(Debug, Clone)]
+pub enum LibraryCommand<'a> {
+    Move(&'a str, &'a str),
+}
+
+#[derive(Debug, Clone)]
+pub enum DbCommand {
+    Migrate,
+}
+
 #[derive(Debug, Clone)]
 pub enum ConventionCommand {
     Record,
@@ -50,10 +73,14 @@ pub enum ConventionCommand {
 /// A cli command
 #[derive(Debug, Clone)]
 pub enum Command<'a> {
-    Update(Vec<&'a str>, bool),
-    Query(QueryCommand<'a>),
+    Update(Vec<&'a str>, Option<&'a str>, bool, update::Settings, Option<&'a str>),
+    Watch(Vec<&'a str>, update::Settings, Duration),
+    Query(QueryCommand<'a>, Option<&'a str>),
     Convention(ConventionCommand),
     Tag(TagCommand<'a>),
+    Archive(ArchiveCommand<'a>),
+    Library(LibraryCommand<'a>),
+    Db(DbCommand),
     Nop,
 }
 
@@ -69,6 +96,7 @@ pub struct Cli {
     pool: db::SqlitePool,
     dapi: DatabaseLayer,
     conf: Configuration,
+    new_tags: bool,
 }
 
 /// Rusts stdlib has the annoying habit of
@@ -96,7 +124,7 @@ impl Cli {
         let conf = profile!("conf", { Configuration::configure(c)? });
         let pool = profile!("pool", { db::Connection::new_pool(&config.database, 2)? });
         let dapi = profile!("connect", { DatabaseLayer::new(db::Connection(pool.get().expect("database connection failure"))) });
-        Ok(Self { pool, conf, dapi })
+        Ok(Self { pool, conf, dapi, new_tags: false })
     }
 
     pub fn connect(&self) -> db::Connection {
@@ -111,12 +139,75 @@ impl Cli {
         &self.conf
     }
 
-    /// The 'update' command
-    pub fn update(&self, paths: &Vec<&str>) -> Res<()> {
-        self.dapi.update(paths)?;
+    /// Add the scan section of the configuration to the
+    /// given settings, preceded by that of the library
+    fn scan_settings(&self, settings: &update::Settings, library: Option<&config::Library>) -> update::Settings {
+        let mut settings = settings.clone();
+        if let Some(scan) = library.and_then(|l| l.scan.as_ref()) { merge_scan(&mut settings, scan) }
+        merge_scan(&mut settings, &self.conf.scan);
+        settings.roots = self.conf.libraries.iter().map(|l| PathBuf::from(self.root_of(l))).collect();
+        settings
+    }
+
+    /// Update the root of a library
+    fn update_library(&self, library: &config::Library, settings: &update::Settings) -> Res<update::Report> {
+        info!("updating library '{}'", library.name);
+        self.dapi.update_with(&vec![library.root.as_str()], &self.scan_settings(settings, Some(library)))
+    }
+
+    /// Update all libraries, then flag the indexed paths
+    /// outside of them on stderr
+    fn update_libraries(&self, settings: &update::Settings) -> Res<update::Report> {
+        if self.conf.libraries.len() == 0 {
+            return Err(E::ArgumentError { message: "update requires a PATH or configured libraries".into() }.into())
+        }
+        let mut report = update::Report { committed: true, ..update::Report::default() };
+        for library in self.conf.libraries.iter() {
+            report.merge(self.update_library(library, settings)?);
+        }
+        let roots: Vec<String> = self.conf.libraries.iter().map(|l| self.root_of(l)).collect();
+        for path in self.dapi.query_outside(&roots.iter().map(|r| r.as_str()).collect())? {
+            eprintln!("outside of all libraries: '{}'", path);
+        }
+        Ok(report)
+    }
+
+    /// The 'update' command, printing the changes in the
+    /// given format, if any. Skipped paths are always
+    /// reported, on stderr unless a format is given.
+    /// Without paths, the given or all libraries are updated.
+    pub fn update(&self, paths: &Vec<&str>, library: Option<&str>, settings: &update::Settings, format: Option<&str>) -> Res<()> {
+        let report = match library {
+            Some(name) => self.update_library(self.conf.library(name)?, settings)?,
+            None if paths.len() == 0 => self.update_libraries(settings)?,
+            None => self.dapi.update_with(paths, &self.scan_settings(settings, None))?,
+        };
+        let output = match format {
+            Some("json") => format!("{}\n", serde_json::to_string_pretty(&report)?),
+            Some(_) if report.is_empty() => String::new(),
+            Some(_) => format!("{}\n", report.to_text()),
+            None => {
+                for e in report.skipped.iter() { eprintln!("skipped '{}': {}", e.path, e.message); }
+                String::new()
+            },
+        };
+        hide_spurious_pipe_errors(io::stdout().write_all(output.as_bytes()))?;
+        if settings.strict && report.skipped.len() > 0 {
+            return Err(update::error::Error::Skipped { count: report.skipped.len() }.into())
+        }
         Ok(())
     }
 
+    /// The 'watch' command, runs until interrupted
+    pub fn watch(&self, paths: &Vec<&str>, settings: &update::Settings, delay: Duration) -> Res<()> {
+        let settings = self.scan_settings(settings, None);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handler = stop.clone();
+        ctrlc::set_handler(move || handler.store(true, Ordering::SeqCst))?;
+        let mut watch = update::Watch::new(paths, &settings, delay)?;
+        watch.run(&stop, |batch| self.dapi.update_with(batch, &settings).map(|_| ()))
+    }
+
     fn format_tag_statistics(names: (&str, &str), count: usize, percents: (f64, f64)) -> String {
         format!("{:50} | {:>6} | {:>1.3} : {:>1.3}",
             format!("{} : {}", names.0, names.1),
@@ -157,6 +248,24 @@ impl Cli {
         Ok(())
     }
 
+    /// Expand a pipeline, restricting it to a library
+    pub fn pipeline(&self, pipeline: &config::Pipeline, library: Option<&str>) -> Res<config::PipelineBuf> {
+        let pipe = config::PipelineBuf::from_pipeline(pipeline).expand(self.conf.expansions())?;
+        match library {
+            Some(name) => Ok(pipe.within(&self.root_of(self.conf.library(name)?))),
+            None => Ok(pipe),
+        }
+    }
+
+    /// The canonical root of a library, as the index
+    /// knows it
+    fn root_of(&self, library: &config::Library) -> String {
+        match Path::new(&library.root).canonicalize() {
+            Ok(root) => root.to_string_lossy().into(),
+            Err(_) => library.root.clone(),
+        }
+    }
+
     /// The 'query' command
     pub fn query(&self, pipeline: config::PipelineBuf, count: bool) -> Res<()> {
         let pipe = query::Pipeline::from_pipeline(pipeline)?;
@@ -187,12 +296,20 @@ impl Cli {
 
     /// The 'map' subcommand
     pub fn query_map(&mut self, pipeline: config::PipelineBuf, action: config::CommandAction, commit: bool) -> Res<()> {
+        // Only mapping adds tags, so only it loads the vocabulary
+        let vocabulary = self.conf.vocabulary.clone().filter(|_| !self.new_tags);
+        profile!("vocabulary", { self.dapi.set_vocabulary(vocabulary)? });
         let summary = self.dapi.query_map(query::Pipeline::from_pipeline(pipeline)?, action, commit)?;
         let s = summary.format();
         println!("{}", s);
         Ok(())
     }
 
+    /// Allow tags outside the vocabulary
+    pub fn allow_new_tags(&mut self) {
+        self.new_tags = true;
+    }
+
     /// The 'clean' command
     pub fn clean(&mut self) -> Res<usize> {
         match self.dapi.clean() {
@@ -201,6 +318,81 @@ impl Cli {
         }
     }
 
+    /// Canonicalize an optional root, defaulting to the cwd
+    fn root(root: Option<&str>) -> Res<PathBuf> {
+        match root {
+            Some(root) => Ok(Path::new(root).canonicalize()?),
+            None => Ok(std::env::current_dir()?),
+        }
+    }
+
+    /// The 'export' command
+    pub fn export(&self, pipeline: Option<config::PipelineBuf>, subtree: Option<&str>, root: Option<&str>, output: Option<&str>) -> Res<()> {
+        let root = Self::root(root.or(subtree))?;
+        let mut manifest = Manifest::new(&root);
+        if let Some(subtree) = subtree {
+            manifest.add_subtree(subtree);
+        }
+        if let Some(pipeline) = pipeline {
+            let pipe = query::Pipeline::from_pipeline(pipeline)?;
+            for file in self.dapi.query_collect(&pipe, collect::TagFiles)??.iter() {
+                manifest.add(file);
+            }
+        }
+        info!("exporting {} File(s)", manifest.files.len());
+        let json = manifest.to_json()?;
+        match output {
+            Some(path) => std::fs::write(path, json)?,
+            None => hide_spurious_pipe_errors(writeln!(io::stdout(), "{}", json))?,
+        }
+        Ok(())
+    }
+
+    /// The 'import' command
+    pub fn import(&self, manifest: &str, root: Option<&str>, commit: bool) -> Res<()> {
+        let manifest = Manifest::read(Path::new(manifest))?;
+        let root = match root {
+            Some(_) => Self::root(root)?,
+            None => PathBuf::from(&manifest.root),
+        };
+        let mut restore = Restore::new(&root);
+        let mut touched = Vec::new();
+        for entry in manifest.files.iter() {
+            let path = match restore.locate(entry) {
+                Located::Path(path) => path,
+                Located::Hash(path) => {
+                    println!("MOVED: {} -> {}", entry.path, path.to_string_lossy());
+                    path
+                },
+                Located::Missing => {
+                    println!("MISSING: {}", entry.path);
+                    continue
+                },
+            };
+            if Restore::apply(&path, entry, commit)? {
+                println!("RESTORE: {}", path.to_string_lossy());
+                touched.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if commit && touched.len() > 0 {
+            self.dapi.update(&touched.iter().map(|p| p.as_str()).collect())?;
+        }
+        Ok(())
+    }
+
+    /// The 'library move' subcommand. The old root may be
+    /// gone already, the new one has to exist.
+    pub fn library_move(&self, from: &str, to: &str) -> Res<()> {
+        let from = from.trim_end_matches(std::path::MAIN_SEPARATOR);
+        let to = Self::root(Some(to))?.to_string_lossy().into_owned();
+        let moved = self.dapi.move_root(from, &to)?;
+        println!("MOVE: {} -> {} ({} File(s))", from, to, moved);
+        for library in self.conf.libraries.iter().filter(|l| l.root.trim_end_matches(std::path::MAIN_SEPARATOR) == from) {
+            eprintln!("the root of library '{}' in the configuration is still '{}'", library.name, library.root);
+        }
+        Ok(())
+    }
+
     /// The 'enforce' subcommand
     pub fn enforce(&mut self, commit: bool) -> Res<()> {
         let reports = self.dapi.enforce(&self.conf.conventions, commit)?;
@@ -212,6 +404,100 @@ impl Cli {
     }
 }
 
+/// Add a scan section of the configuration to settings,
+/// options already set take precedence
+fn merge_scan(settings: &mut update::Settings, scan: &config::Scan) {
+    settings.ignore.patterns.extend(scan.ignore.iter().flatten().cloned());
+    settings.ignore.gitignore |= scan.gitignore.unwrap_or(false);
+    let traversal = &mut settings.traversal;
+    traversal.follow_links |= scan.follow_links.unwrap_or(false);
+    traversal.one_file_system |= scan.one_file_system.unwrap_or(false);
+    traversal.max_depth = traversal.max_depth.or(scan.max_depth);
+    traversal.min_depth = traversal.min_depth.or(scan.min_depth);
+}
+
+/// Settings shared by the 'update' and 'watch' commands
+fn scan_settings(o: &Options) -> Res<update::Settings> {
+    Ok(update::Settings {
+        fingerprint: o.flag("fingerprint"),
+        full: o.flag("full"),
+        threads: o.opt("threads").map(|t| t.parse()).transpose()?.unwrap_or(0),
+        ignore: update::Ignore {
+            patterns: o.opt_vec("ignore").unwrap_or_default().iter().map(|p| p.to_string()).collect(),
+            gitignore: o.flag("gitignore"),
+        },
+        traversal: update::Traversal {
+            follow_links: o.flag("follow-links"),
+            one_file_system: o.flag("one-file-system"),
+            max_depth: o.opt("max-depth").map(|d| d.parse()).transpose()?,
+            min_depth: o.opt("min-depth").map(|d| d.parse()).transpose()?,
+        },
+        dry_run: false,
+        strict: false,
+        batch: 0,
+        roots: Vec::new(),
+        stores: None,
+    })
+}
+
+/// Arguments shared by the 'update' and 'watch' commands
+fn scan_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("fingerprint")
+            .long("fingerprint")
+            .help("Fingerprint file contents to detect moves across filesystems")
+            .takes_value(false),
+        Arg::with_name("full")
+            .long("full")
+            .help("Re-read the tags of all files, even unchanged ones")
+            .takes_value(false),
+        Arg::with_name("threads")
+            .short("j")
+            .long("threads")
+            .help("The number of threads used for scanning, defaults to one per CPU")
+            .takes_value(true),
+        Arg::with_name("ignore")
+            .short("i")
+            .long("ignore")
+            .help("Ignore paths matching a gitignore-style pattern")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true),
+        Arg::with_name("gitignore")
+            .long("gitignore")
+            .help("Honour .gitignore files as well as .tagignore files")
+            .takes_value(false),
+        Arg::with_name("follow-links")
+            .short("L")
+            .long("follow-links")
+            .help("Follow symbolic links")
+            .takes_value(false),
+        Arg::with_name("one-file-system")
+            .short("x")
+            .long("one-file-system")
+            .help("Don't descend into other filesystems")
+            .takes_value(false),
+        Arg::with_name("max-depth")
+            .long("max-depth")
+            .help("Skip entries more than N levels below the given paths")
+            .value_name("N")
+            .takes_value(true),
+        Arg::with_name("min-depth")
+            .long("min-depth")
+            .help("Skip entries less than N levels below the given paths")
+            .value_name("N")
+            .takes_value(true),
+    ]
+}
+
+/// The 'db migrate' subcommand
+fn db_migrate(config: &Config) -> Res<()> {
+    let output = tag_suite::db::migrate::open(&config.database)?;
+    print!("{}", output);
+    println!("SCHEMA: {}", tag_suite::db::migrate::SCHEMA_VERSION);
+    Ok(())
+}
+
 fn cli(options: &ArgMatches) -> Res<()> {
     //{{{ Options
     let o = Options::new(options);
@@ -225,7 +511,19 @@ fn cli(options: &ArgMatches) -> Res<()> {
         };
         let command = {
             if let Some(options) = options.subcommand_matches("update") {
-                oo = Options::new(options); Command::Update(oo.vec("PATH"), oo.flag("clean"))
+                oo = Options::new(options);
+                let settings = update::Settings {
+                    dry_run: oo.flag("dry-run"),
+                    strict: oo.flag("strict"),
+                    batch: oo.opt("batch").map(|b| b.parse()).transpose()?.unwrap_or(0),
+                    ..scan_settings(&oo)?
+                };
+                let report = oo.opt("report").or(if settings.dry_run { Some("text") } else { None });
+                Command::Update(oo.opt_vec("PATH").unwrap_or_default(), oo.opt("library"), oo.flag("clean"), settings, report)
+            } else if let Some(options) = options.subcommand_matches("watch") {
+                oo = Options::new(options);
+                let delay = Duration::from_millis(oo.get("delay").parse()?);
+                Command::Watch(oo.vec("PATH"), scan_settings(&oo)?, delay)
             } else if let Some(options) = options.subcommand_matches("tag") {
                 /* oo = Options::new(options); */
                 if let Some(_options) = options.subcommand_matches("clean") {
@@ -242,15 +540,17 @@ fn cli(options: &ArgMatches) -> Res<()> {
                 oo = Options::new(options);
                 if let Some(_options) = options.subcommand_matches("count") {
                     let pipeline = config::Pipeline::from_options(&oo);
-                    Command::Query(QueryCommand::Count(pipeline))
+                    Command::Query(QueryCommand::Count(pipeline), oo.opt("library"))
                 } else if let Some(options) = options.subcommand_matches("serialize") {
                     ooo = Options::new(options);
                     let pipeline = config::Pipeline::from_options(&oo);
-                    Command::Query(QueryCommand::Serialize(pipeline, ooo.opt("FORMAT")))
+                    Command::Query(QueryCommand::Serialize(pipeline, ooo.opt("FORMAT")), oo.opt("library"))
                 } else if let Some(options) = options.subcommand_matches("map") {
                     ooo = Options::new(options);
+                    let mut new = false;
                     let map = if let Some(options) = options.subcommand_matches("add") {
-                        oooo = Options::new(options); config::CommandAction::Add(oooo.vec("TAGS"))
+                        oooo = Options::new(options); new = oooo.flag("new");
+                        config::CommandAction::Add(oooo.vec("TAGS"))
                     } else if let Some(_options) = options.subcommand_matches("forget") {
                         /* oooo = Options::new(options); */ config::CommandAction::Forget
                     } else if let Some(options) = options.subcommand_matches("del") {
@@ -267,11 +567,21 @@ fn cli(options: &ArgMatches) -> Res<()> {
                         return Err(E::ArgumentError { message: "map requires a subcommand".into() }.into())
                     };
                     let pipeline = config::Pipeline::from_options(&oo);
-                    Command::Query(QueryCommand::Map(pipeline, map, ooo.flag("commit")))
+                    Command::Query(QueryCommand::Map(pipeline, map, ooo.flag("commit"), new), oo.opt("library"))
                 } else {
                     let pipeline = config::Pipeline::from_options(&oo);
-                    Command::Query(QueryCommand::Output(pipeline))
+                    Command::Query(QueryCommand::Output(pipeline), oo.opt("library"))
                 }
+            } else if let Some(options) = options.subcommand_matches("export") {
+                oo = Options::new(options);
+                if oo.opt("QUERY").is_none() && oo.opt("subtree").is_none() {
+                    return Err(E::ArgumentError { message: "export requires a QUERY or a subtree".into() }.into())
+                }
+                let pipeline = config::Pipeline::from_options(&oo);
+                Command::Archive(ArchiveCommand::Export(pipeline, oo.opt("subtree"), oo.opt("root"), oo.opt("output")))
+            } else if let Some(options) = options.subcommand_matches("import") {
+                oo = Options::new(options);
+                Command::Archive(ArchiveCommand::Import(oo.get("MANIFEST"), oo.opt("root"), oo.flag("commit")))
             } else if let Some(options) = options.subcommand_matches("convention") {
                 /* oo = Options::new(options); */
                 if let Some(options) = options.subcommand_matches("enforce") {
@@ -281,6 +591,18 @@ fn cli(options: &ArgMatches) -> Res<()> {
                 } else {
                     return Err(E::ArgumentError { message: "convention requires a subcommand".into() }.into())
                 }
+            } else if let Some(options) = options.subcommand_matches("library") {
+                if let Some(options) = options.subcommand_matches("move") {
+                    ooo = Options::new(options); Command::Library(LibraryCommand::Move(ooo.get("OLD"), ooo.get("NEW")))
+                } else {
+                    return Err(E::ArgumentError { message: "library requires a subcommand".into() }.into())
+                }
+            } else if let Some(options) = options.subcommand_matches("db") {
+                if let Some(_options) = options.subcommand_matches("migrate") {
+                    Command::Db(DbCommand::Migrate)
+                } else {
+                    return Err(E::ArgumentError { message: "db requires a subcommand".into() }.into())
+                }
             } else { Command::Nop }
         };
         info!("command: {:?}", command);
@@ -289,34 +611,37 @@ fn cli(options: &ArgMatches) -> Res<()> {
         (config, command)
     });
     //}}}
+    // Opening the database migrates it, so do it here
+    // to report what was applied.
+    if let Command::Db(DbCommand::Migrate) = command { return db_migrate(&config) }
     let mut cli = profile!("configure", { Cli::new(&config) })?;
     //{{{ Command
     profile!("command", {
         match command {
-            Command::Update(paths, clean) => {
-                cli.update(&paths)?;
+            Command::Update(paths, library, clean, settings, report) => {
+                cli.update(&paths, library, &settings, report)?;
                 if clean { cli.clean()?; }
             }
-            Command::Query(q) => {
+            Command::Watch(paths, settings, delay) => {
+                cli.watch(&paths, &settings, delay)?;
+            }
+            Command::Query(q, library) => {
                 match q {
                     QueryCommand::Output(pipeline) => {
-                        let pipe = config::PipelineBuf::from_pipeline(&pipeline)
-                            .expand(cli.config().expansions())?;
+                        let pipe = cli.pipeline(&pipeline, library)?;
                         cli.query(pipe, false)?;
                     }
-                    QueryCommand::Map(pipeline, map, commit) => {
-                        let pipe = config::PipelineBuf::from_pipeline(&pipeline)
-                            .expand(cli.config().expansions())?;
+                    QueryCommand::Map(pipeline, map, commit, new) => {
+                        let pipe = cli.pipeline(&pipeline, library)?;
+                        if new { cli.allow_new_tags(); }
                         cli.query_map(pipe, map, commit)?;
                     }
                     QueryCommand::Count(pipeline) => {
-                        let pipe = config::PipelineBuf::from_pipeline(&pipeline)
-                            .expand(cli.config().expansions())?;
+                        let pipe = cli.pipeline(&pipeline, library)?;
                         cli.query(pipe, true)?;
                     }
                     QueryCommand::Serialize(pipeline, format) => {
-                        let pipe = config::PipelineBuf::from_pipeline(&pipeline)
-                            .expand(cli.config().expansions())?;
+                        let pipe = cli.pipeline(&pipeline, library)?;
                         cli.query_serialize(pipe, format)?;
                     }
                 }
@@ -330,13 +655,27 @@ fn cli(options: &ArgMatches) -> Res<()> {
             Command::Tag(TagCommand::Statistics(tag)) => {
                 cli.tag_statistics(tag)?;
             }
+            Command::Archive(ArchiveCommand::Export(pipeline, subtree, root, output)) => {
+                let pipe = match pipeline.query {
+                    Some(_) => Some(config::PipelineBuf::from_pipeline(&pipeline)
+                        .expand(cli.config().expansions())?),
+                    None => None,
+                };
+                cli.export(pipe, subtree, root, output)?;
+            }
+            Command::Archive(ArchiveCommand::Import(manifest, root, commit)) => {
+                cli.import(manifest, root, commit)?;
+            }
             Command::Convention(ConventionCommand::Enforce(commit)) => {
                 cli.enforce(commit)?;
             }
             Command::Convention(ConventionCommand::Record) => {
                 //cli.enforce(commit)?;
             }
-            Command::Nop => {}
+            Command::Library(LibraryCommand::Move(from, to)) => {
+                cli.library_move(from, to)?;
+            }
+            Command::Db(_) | Command::Nop => {}
         }
     });
     //}}}
@@ -365,6 +704,24 @@ fn main() -> Res<()> {
             .subcommand(SubCommand::with_name("config")
                 .about("Configuration subcommand [unimplemented]"))
 
+            .subcommand(SubCommand::with_name("library")
+                .about("Library subcommand")
+                .subcommand(SubCommand::with_name("move")
+                    .about("Move the indexed files below OLD to NEW, without a rescan")
+                    .arg(Arg::with_name("OLD")
+                        .help("The old root")
+                        .required(true)
+                        .takes_value(true))
+                    .arg(Arg::with_name("NEW")
+                        .help("The new root")
+                        .required(true)
+                        .takes_value(true))))
+
+            .subcommand(SubCommand::with_name("db")
+                .about("Database subcommand")
+                .subcommand(SubCommand::with_name("migrate")
+                    .about("Create the database or apply its pending schema migrations")))
+
             .subcommand(SubCommand::with_name("convention")
                 .about("Convention subcommand")
                 .subcommand(SubCommand::with_name("enforce")
@@ -399,13 +756,113 @@ fn main() -> Res<()> {
                     .long("clean")
                     .help("Clean tags after update")
                     .takes_value(false))
+                .args(&scan_args())
+                .arg(Arg::with_name("dry-run")
+                    .short("n")
+                    .long("dry-run")
+                    .help("Report the changes without committing them")
+                    .takes_value(false))
+                .arg(Arg::with_name("strict")
+                    .long("strict")
+                    .help("Roll back if any path was skipped")
+                    .takes_value(false))
+                .arg(Arg::with_name("batch")
+                    .long("batch")
+                    .help("Commit every N files, resuming an interrupted update")
+                    .value_name("N")
+                    .takes_value(true))
+                .arg(Arg::with_name("report")
+                    .long("report")
+                    .help("Print the changes in the given FORMAT, text unless given")
+                    .value_name("FORMAT")
+                    .possible_values(&["text", "json"])
+                    .takes_value(true))
+                .arg(Arg::with_name("library")
+                    .short("l")
+                    .long("library")
+                    .help("Update the configured library NAME")
+                    .value_name("NAME")
+                    .conflicts_with("PATH")
+                    .takes_value(true))
                 .arg(Arg::with_name("PATH")
-                    .help("The paths to update")
+                    .help("The paths to update, all libraries unless given")
                     .takes_value(true)
                     .multiple(true)))
 
+            .subcommand(SubCommand::with_name("watch")
+                .about("Keeps the database up to date while files change")
+                .args(&scan_args())
+                .arg(Arg::with_name("delay")
+                    .long("delay")
+                    .help("Wait for MS milliseconds without changes before updating")
+                    .value_name("MS")
+                    .default_value("1000")
+                    .takes_value(true))
+                .arg(Arg::with_name("PATH")
+                    .help("The paths to watch")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple(true)))
+
+            .subcommand(SubCommand::with_name("export")
+                .about("Export the tags of a query or subtree to a JSON manifest")
+                .arg(Arg::with_name("filter")
+                    .short("f")
+                    .long("filter")
+                    .help("Filter the results using EXPR")
+                    .value_name("EXPR")
+                    .takes_value(true))
+                .arg(Arg::with_name("subtree")
+                    .short("s")
+                    .long("subtree")
+                    .help("Export all files below DIR")
+                    .value_name("DIR")
+                    .takes_value(true))
+                .arg(Arg::with_name("root")
+                    .short("r")
+                    .long("root")
+                    .help("Store paths relative to DIR")
+                    .value_name("DIR")
+                    .takes_value(true))
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .help("Write the manifest to FILE")
+                    .value_name("FILE")
+                    .takes_value(true))
+                .arg(Arg::with_name("QUERY")
+                    .help("The QUERY to export")
+                    .takes_value(true)))
+
+            .subcommand(SubCommand::with_name("import")
+                .about("Re-apply the tags of a JSON manifest")
+                .arg(Arg::with_name("root")
+                    .short("r")
+                    .long("root")
+                    .help("Resolve paths relative to DIR")
+                    .value_name("DIR")
+                    .takes_value(true))
+                .arg(Arg::with_name("commit")
+                    .short("c")
+                    .long("commit")
+                    .help("Commit the results"))
+                .arg(Arg::with_name("MANIFEST")
+                    .help("The manifest to import")
+                    .required(true)
+                    .takes_value(true)))
+
             .subcommand(SubCommand::with_name("query")
                 .about("Queries the database and prints the results")
+                .arg(Arg::with_name("library")
+                    .short("l")
+                    .long("library")
+                    .help("Restrict the results to the configured library NAME")
+                    .value_name("NAME")
+                    .takes_value(true))
+                .arg(Arg::with_name("include-offline")
+                    .long("include-offline")
+                    .help("Include files on unmounted volumes")
+                    .takes_value(false))
                 .arg(Arg::with_name("filter")
                     .short("f")
                     .long("filter")
@@ -445,6 +902,10 @@ fn main() -> Res<()> {
                     .subcommand(SubCommand::with_name("forget"))
                     .subcommand(SubCommand::with_name("emit"))
                     .subcommand(SubCommand::with_name("add")
+                        .arg(Arg::with_name("new")
+                            .short("n")
+                            .long("new")
+                            .help("Allow tags outside the vocabulary"))
                         .arg(Arg::with_name("TAGS")
                             .help("The TAGs to add")
                             .required(true)
@@ -547,14 +1008,14 @@ pub mod suite {
         };
         b.iter(|| {
             let cli = Cli::new(&config).unwrap();
-            cli.update(&vec![defaults::test_path("files", "1500").as_str()]).unwrap();
+            cli.update(&vec![defaults::test_path("files", "1500").as_str()], None, &update::Settings::default(), None).unwrap();
         });
     }
 
     #[bench]
     #[ignore]
     fn bench_query_all(b: &mut test::Bencher) {
-        let config = Config {
+        let _config = Config {
             config: defaults::test_path("tag", "config.yaml"),
             database: defaults::test_path("tag", "db.sqlite"),
         };
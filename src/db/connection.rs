@@ -18,7 +18,7 @@ impl<C: diesel::Connection, E> CustomizeConnection<C, E> for ConnectionCustomize
     fn on_acquire(&self, connection: &mut C) -> Result<(), E> {
         // FIXME: I cannot for the life of me figure
         // out how to return a proper error here.
-        diesel::dsl::sql_query(format!("PRAGMA foreign_keys = ON"))
+        diesel::dsl::sql_query("PRAGMA foreign_keys = ON".to_string())
             .execute(connection)
             .expect("pragma error: failed to enable foreign key support");
         Ok(())
@@ -29,8 +29,10 @@ pub struct Connection(pub PooledConnection<ConnectionManager<SqliteConnection>>)
 
 impl Connection {
 
-    /// Create a new connection pool.
+    /// Create a new connection pool, migrating the
+    /// database first.
     pub fn new_pool(database_url: &str, max_size: u32) -> Res<SqlitePool> {
+        super::migrate::open(database_url)?;
         let manager = ConnectionManager::<SqliteConnection>::new(database_url);
         Pool::builder()
             .max_size(max_size)
@@ -0,0 +1,59 @@
+use super::{import::*, error::Error};
+use diesel::{Connection, SqliteConnection};
+use diesel_migrations::MigrationConnection;
+
+embed_migrations!("migrations");
+
+/// The version of the latest migration, i.e. the schema
+/// this binary was built against. It is taken from the
+/// migrations directory by the build script.
+pub const SCHEMA_VERSION: &'static str = env!("SCHEMA_VERSION");
+
+/// The schema version of a database, None if it is new
+pub fn version(c: &SqliteConnection) -> Res<Option<String>> {
+    diesel_migrations::setup_database(c)?;
+    Ok(c.latest_run_migration_version()?)
+}
+
+/// Apply all pending migrations, returning their output.
+/// Databases newer than this binary are refused.
+pub fn run(c: &SqliteConnection) -> Res<String> {
+    match version(c)? {
+        Some(ref v) if v.as_str() > SCHEMA_VERSION => {
+            return Err(Error::SchemaTooNew { version: v.clone(), expected: SCHEMA_VERSION.into() }.into())
+        },
+        Some(ref v) if v == SCHEMA_VERSION => return Ok(String::new()),
+        _ => {},
+    }
+    let mut output = Vec::new();
+    embedded_migrations::run_with_output(c, &mut output)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Open a database, creating it if needed, and bring its
+/// schema up to date. Migrations which rebuild tables
+/// must not cascade, so foreign keys stay disabled here.
+pub fn open(database_url: &str) -> Res<String> {
+    if let Some(dir) = Path::new(database_url).parent() {
+        if !dir.as_os_str().is_empty() { std::fs::create_dir_all(dir)?; }
+    }
+    let c = SqliteConnection::establish(database_url)?;
+    let output = run(&c)?;
+    for line in output.lines() { info!("{}", line); }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+
+    #[test]
+    fn check_open() {
+        let (_tmp, dir) = crate::util::testing::temp_dir();
+        let database = dir.join("db.sqlite").to_string_lossy().to_string();
+        open(&database).unwrap();
+        let c = SqliteConnection::establish(&database).unwrap();
+        assert_eq!(version(&c).unwrap(), Some(SCHEMA_VERSION.to_string()));
+        assert_eq!(run(&c).unwrap(), "");
+    }
+}
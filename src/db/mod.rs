@@ -1,11 +1,14 @@
 pub mod schema;
 pub mod connection;
+pub mod migrate;
 
 pub mod error {
     #[derive(Debug, Fail)]
     pub enum Error {
         #[fail(display = "connection pool error: '{}'", message)]
         ConnectionPoolError { message: String, },
+        #[fail(display = "database schema {} is newer than this binary ({})", version, expected)]
+        SchemaTooNew { version: String, expected: String, },
     }
 }
 
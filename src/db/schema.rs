@@ -10,6 +10,24 @@ table! {
         id -> BigInt,
         kind -> BigInt,
         path -> Text,
+        device -> Nullable<BigInt>,
+        inode -> Nullable<BigInt>,
+        fingerprint -> Nullable<Text>,
+        ctime -> Nullable<BigInt>,
+        mtime -> Nullable<BigInt>,
+        size -> Nullable<BigInt>,
+        offline -> Bool,
+        root_id -> Nullable<BigInt>,
+        uid -> Nullable<BigInt>,
+        gid -> Nullable<BigInt>,
+        mode -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    roots (id) {
+        id -> BigInt,
+        path -> Text,
     }
 }
 
@@ -17,14 +35,25 @@ table! {
     tags (id) {
         id -> BigInt,
         name -> Text,
+        value -> Nullable<Text>,
+    }
+}
+
+table! {
+    update_progress (root) {
+        root -> Text,
+        path -> Text,
     }
 }
 
 joinable!(file_tags -> files (file_id));
+joinable!(files -> roots (root_id));
 joinable!(file_tags -> tags (tag_id));
 
 allow_tables_to_appear_in_same_query!(
     file_tags,
     files,
+    roots,
     tags,
+    update_progress,
 );
@@ -1,7 +1,7 @@
 use super::{import::*, error::{Error as E}};
 use std::cell::RefCell;
-use diesel::prelude::*;
-use crate::{db::schema::{files, tags, file_tags}, model::file_tag::{BooleanJoined as Boolean, SelectJoined as Select}, expression::{Logic, Ast}};
+use diesel::{prelude::*, expression::SqlLiteral, sql_types::Text};
+use crate::{db::schema::{files, tags, file_tags}, model::{file::meta_text, file_tag::{BooleanJoined as Boolean, SelectJoined as Select}, root::absolute_path}, expression::{Logic, Ast}};
 use crate::expression::{Namespec};
 
 /// Custom querying Dsl
@@ -31,7 +31,7 @@ impl<'a> Dsl<'a> {
     }
 
     pub fn evaluate(&self, ast: &Ast, context: &RefCell<()>, input: &()) -> Res<Boolean<'a>> {
-        Ok(self.0.evaluate(ast, context, input)?)
+        self.0.evaluate(ast, context, input)
     }
 
     /// Select file ids
@@ -46,7 +46,15 @@ impl<'a> Dsl<'a> {
     /// Select file ids WHERE file.path LIKE
     fn path_fids(exp: &str) -> Select<'a> {
         use crate::util::sql::sql_text;
-        let concat = sql_text("::").concat(files::path);
+        let concat = sql_text("::").concat(absolute_path());
+        Self::fids().filter(concat.like(exp.to_string()))
+    }
+
+    /// Select file ids WHERE the stat of a file, as text,
+    /// is LIKE
+    fn meta_fids(text: SqlLiteral<Text>, exp: &str) -> Select<'a> {
+        use crate::util::sql::sql_text;
+        let concat = sql_text("::").concat(text);
         Self::fids().filter(concat.like(exp.to_string()))
     }
 
@@ -67,6 +75,13 @@ impl<'a> Dsl<'a> {
                 match canonical.get_reserved().as_str() {
                     RESERVED_TAG => { Ok(Self::tags_fids(&user.to_string())) }
                     RESERVED_PATH => { Ok(Self::path_fids(&user.to_string())) }
+                    RESERVED_STAT => {
+                        let (stat, value) = canonical.to_statspace();
+                        match meta_text(stat) {
+                            Some(text) => { Ok(Self::meta_fids(text, &value.to_string())) }
+                            None => { Err(E::InvalidNamespace{ name: format!("{}::{}", RESERVED_STAT, stat) }.into()) }
+                        }
+                    }
                     e => { Err(E::InvalidNamespace{ name: e.into() }.into()) }
                 }
             },
@@ -69,6 +69,14 @@ impl<'q> Dsl<'q> {
         use crate::expression::namespace::constants::*;
         let mut context = context.borrow_mut();
         let (canon, user) = Namespec::canonicalize_user_expression(exp);
+        if canon.get_reserved().as_str() == RESERVED_STAT {
+            let (stat, value) = canon.to_statspace();
+            if !STATS.contains(&stat) {
+                return Err(E::InvalidNamespace{ name: format!("{}::{}", RESERVED_STAT, stat) }.into())
+            }
+            let regex = context.regex(&value.to_string()[2..])?;
+            return Ok(attr.meta_text(stat).map_or(false, |text| regex.is_match(&text)))
+        }
         let regex = context.regex(&user.to_string()[2..])?;
         match canon.get_reserved().as_str() {
             RESERVED_TAG => {
@@ -78,7 +86,7 @@ impl<'q> Dsl<'q> {
                 Ok(false)
             }
             RESERVED_PATH => {
-                Ok(regex.is_match(&attr.path()))
+                Ok(regex.is_match(attr.path()))
             }
             e => { Err(E::InvalidNamespace{ name: e.into() }.into()) }
         }
@@ -89,7 +97,7 @@ impl<'q> Dsl<'q> {
         let mut context = context.borrow_mut();
         let comparison = context.comparison(exp)?;
         let comparables: Comparables = attr.parameters().into();
-        Ok(comparables.evaluate(comparison)?)
+        comparables.evaluate(comparison, |variable| attr.is_known(variable))
     }
 
     /// Evaluate the parameter comparison expression
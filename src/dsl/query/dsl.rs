@@ -1,5 +1,6 @@
 use super::{import::*, Context, error::{Error as E}};
 use crate::{db::export::*, model::export::*, expression::export::*};
+use diesel::{expression::SqlLiteral, sql_types::Text};
 use std::cell::RefCell;
 
 /// Custom querying Dsl.
@@ -29,7 +30,7 @@ impl<'a> Dsl<'a> {
     }
 
     pub fn evaluate(&self, ast: &Ast, context: &RefCell<Context<'a>>, input: &()) -> Res<Boolean<'a>> {
-        Ok(self.0.evaluate(ast, context, input)?)
+        self.0.evaluate(ast, context, input)
     }
 
     /// Select file ids WHERE file.kind = ?.
@@ -54,7 +55,18 @@ impl<'a> Dsl<'a> {
     /// Select file ids WHERE file.path LIKE.
     fn path_fids(exp: &str, c: &db::Connection) -> Res<Vec<i64>> {
         use crate::util::sql::sql_text;
-        let concat = sql_text("::").concat(files::path);
+        let concat = sql_text("::").concat(absolute_path());
+        Ok(files::table
+            .select(files::id)
+            .filter(concat.like(exp.to_string()))
+            .get_results(c.get())?)
+    }
+
+    /// Select file ids WHERE the stat of a file, as text,
+    /// is LIKE.
+    fn meta_fids(text: SqlLiteral<Text>, exp: &str, c: &db::Connection) -> Res<Vec<i64>> {
+        use crate::util::sql::sql_text;
+        let concat = sql_text("::").concat(text);
         Ok(files::table
             .select(files::id)
             .filter(concat.like(exp.to_string()))
@@ -90,6 +102,13 @@ impl<'a> Dsl<'a> {
                     RESERVED_TAG => { Self::tags_fids(&user.to_string(), c)? }
                     RESERVED_PATH => { Self::path_fids(&user.to_string(), c)? }
                     RESERVED_KIND => { Self::kind_fids(&user.to_string(), c)? }
+                    RESERVED_STAT => {
+                        let (stat, value) = canonical.to_statspace();
+                        match meta_text(stat) {
+                            Some(text) => { Self::meta_fids(text, &value.to_string(), c)? }
+                            None => { return Err(E::InvalidNamespace{ name: format!("{}::{}", RESERVED_STAT, stat) }.into()) }
+                        }
+                    }
                     e => { return Err(E::InvalidNamespace{ name: e.into() }.into()) }
                 };
                 Ok(files::table.filter(files::id.eq_any(fids)).select(files::id).into_boxed())
@@ -1,13 +1,14 @@
 use super::{import::*, error::{Error as E}};
 use regex::Regex;
+use std::cmp::Ordering;
 
-const VARIABLE: &'static str = r"[a-zA-Z0-9\.-_]+";
-const OPERATOR: &'static str = r"<=|>=|==|<|>";
-const NUMBER: &'static str = r"[0-9+]";
+const VARIABLE: &'static str = r"[a-zA-Z0-9_\.:-]+";
+const OPERATOR: &'static str = r"<=|>=|==|!=|<|>";
+const LITERAL: &'static str = r"[^\s]+";
 
 lazy_static! {
     static ref COMPARISON: Regex = {
-        Regex::new(&format!(r"({}) *({}) *({})", VARIABLE, OPERATOR, NUMBER))
+        Regex::new(&format!(r"^\s*({}) *({}) *({})\s*$", VARIABLE, OPERATOR, LITERAL))
             .expect("failed to compile regex")
     };
 }
@@ -16,11 +17,62 @@ lazy_static! {
 #[derive(Copy, Clone)]
 enum Comparator { Eq, Ne, Lt, Le, Ge, Gt }
 
+/// A typed value. Values are compared numerically
+/// if both sides are numbers and lexically if both
+/// sides are text. Mixed comparisons never match,
+/// not even with `!=`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl Value {
+
+    /// Interpret a string as the narrowest fitting type
+    pub fn parse(s: &str) -> Self {
+        if let Ok(i) = s.parse::<i64>() {
+            Value::Integer(i)
+        } else {
+            match s.parse::<f64>() {
+                Ok(r) if r.is_finite() => Value::Real(r),
+                _ => Value::Text(s.into()),
+            }
+        }
+    }
+
+    /// Return the numeric value, so it is a number
+    pub fn as_real(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Real(r) => Some(*r),
+            Value::Text(_) => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.partial_cmp(b),
+            (a, b) => a.as_real()?.partial_cmp(&b.as_real()?),
+        }
+    }
+}
+
 /// A compiled Comparison expression
 pub struct Comparison {
     cmp: Comparator,
     lhs: String,
-    rhs: usize,
+    rhs: Value,
 }
 
 impl Comparison {
@@ -47,35 +99,32 @@ impl Comparison {
         Ok(Self {
             lhs: lhs.into(),
             cmp: Comparables::operator(cmp)?,
-            rhs: Comparables::literal(rhs)?,
+            rhs: Comparables::literal(rhs),
         })
     }
 }
 
 /// A collection of variables for comparison
 #[derive(Debug)]
-pub struct Comparables {
-    variables: HashMap<&'static str, usize>,
+pub struct Comparables<'p> {
+    variables: HashMap<&'p str, Value>,
 }
 
-impl Comparables {
+impl<'p> Comparables<'p> {
 
     /// Create a new collection of Comparables from the given Map
-    pub fn from_map(variables: HashMap<&'static str, usize>) -> Self {
+    pub fn from_map(variables: HashMap<&'p str, Value>) -> Self {
         Self { variables }
     }
 
     /// Interpret a variable
-    fn variable(&self, variable: &str) -> Res<usize> {
-        match self.variables.get(variable) {
-            Some(number) => Ok(*number),
-            None => Err(E::UnknownVariable { variable: variable.into() }.into())
-        }
+    fn variable(&self, variable: &str) -> Option<&Value> {
+        self.variables.get(variable)
     }
 
     /// Interpret a literal
-    fn literal(literal: &str) -> Res<usize> {
-        Ok(literal.parse::<usize>()?)
+    fn literal(literal: &str) -> Value {
+        Value::parse(literal)
     }
 
     /// Interpret the operator string
@@ -91,32 +140,103 @@ impl Comparables {
         }
     }
 
-    /// Perform the comparison
-    fn operation(lhs: usize, cmp: Comparator, rhs: usize) -> bool {
+    /// Perform the comparison, values of different types are unordered
+    fn operation(lhs: &Value, cmp: Comparator, rhs: &Value) -> bool {
+        let ordering = match lhs.partial_cmp(rhs) {
+            Some(ordering) => ordering,
+            None => return false,
+        };
         match cmp {
-            Comparator::Eq => lhs == rhs,
-            Comparator::Ne => lhs != rhs,
-            Comparator::Ge => lhs >= rhs,
-            Comparator::Le => lhs <= rhs,
-            Comparator::Gt => lhs > rhs,
-            Comparator::Lt => lhs < rhs,
+            Comparator::Eq => ordering == Ordering::Equal,
+            Comparator::Ne => ordering != Ordering::Equal,
+            Comparator::Ge => ordering != Ordering::Less,
+            Comparator::Le => ordering != Ordering::Greater,
+            Comparator::Gt => ordering == Ordering::Greater,
+            Comparator::Lt => ordering == Ordering::Less,
         }
     }
 
-    /// Evaluate the comparison
-    pub fn evaluate(&self, comparison: &Comparison) -> Res<bool> {
-        Ok(Self::operation(self.variable(&comparison.lhs)?, comparison.cmp, comparison.rhs))
+    /// Evaluate the comparison. Since most variables stem
+    /// from key=value tags, a missing variable that is still
+    /// `known` simply doesn't match, any other is an error.
+    pub fn evaluate(&self, comparison: &Comparison, known: impl Fn(&str) -> bool) -> Res<bool> {
+        match self.variable(&comparison.lhs) {
+            Some(lhs) => Ok(Self::operation(lhs, comparison.cmp, &comparison.rhs)),
+            None if known(&comparison.lhs) => Ok(false),
+            None => Err(E::UnknownVariable { variable: comparison.lhs.clone() }.into()),
+        }
     }
 }
 
 /// Create Comparables from a Map of variables
-impl From<HashMap<&'static str, usize>> for Comparables {
-    fn from(variables: HashMap<&'static str, usize>) -> Self {
+impl<'p> From<HashMap<&'p str, Value>> for Comparables<'p> {
+    fn from(variables: HashMap<&'p str, Value>) -> Self {
         Self::from_map(variables)
     }
 }
 
 /// A type with Comparable Parameters
 pub trait Parameters {
-    fn parameters(&self) -> HashMap<&'static str, usize>;
+    fn parameters<'p>(&'p self) -> HashMap<&'p str, Value>;
+    /// Whether the variable may be compared, even if this lacks it
+    fn is_known(&self, variable: &str) -> bool;
+}
+
+#[cfg(test)]
+mod suite {
+
+    use super::*;
+
+    fn evaluate(expression: &str, variables: Vec<(&'static str, &str)>, known: &[&str]) -> Res<bool> {
+        let comparison = Comparison::new(expression).unwrap();
+        let comparables = Comparables::from_map(
+            variables.into_iter().map(|(k, v)| (k, Value::parse(v))).collect()
+        );
+        comparables.evaluate(&comparison, |variable| known.contains(&variable))
+    }
+
+    fn compare(expression: &str, variables: Vec<(&'static str, &str)>) -> bool {
+        evaluate(expression, variables, &[]).unwrap()
+    }
+
+    #[test]
+    fn check_integer_comparison() {
+        assert_eq!(compare("Rating >= 4", vec![("Rating", "4")]), true);
+        assert_eq!(compare("Rating >= 4", vec![("Rating", "3")]), false);
+        assert_eq!(compare("tags.len > 10", vec![("tags.len", "12")]), true);
+        assert_eq!(compare("tags.len!=12", vec![("tags.len", "12")]), false);
+    }
+
+    #[test]
+    fn check_mixed_comparison() {
+        assert_eq!(compare("Score < 4", vec![("Score", "3.5")]), true);
+        assert_eq!(compare("Score == 3.0", vec![("Score", "3")]), true);
+        assert_eq!(compare("Score > 2", vec![("Score", "high")]), false);
+        assert_eq!(compare("Score != 2", vec![("Score", "high")]), false);
+        assert_eq!(compare("Score == 2", vec![("Score", "high")]), false);
+    }
+
+    #[test]
+    fn check_text_comparison() {
+        assert_eq!(compare("Genre == Rock", vec![("Genre", "Rock")]), true);
+        assert_eq!(compare("Video::Lang != de", vec![("Video::Lang", "en")]), true);
+    }
+
+    #[test]
+    fn check_missing_variable() {
+        assert_eq!(evaluate("Rating >= 4", vec![("Score", "5")], &["Rating"]).unwrap(), false);
+        assert_eq!(evaluate("Rating != 4", vec![("Score", "5")], &["Rating"]).unwrap(), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "UnknownVariable")]
+    fn check_unknown_variable() {
+        evaluate("Ratnig >= 4", vec![("Rating", "5")], &["Rating"]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "FailedCapture")]
+    fn check_invalid_comparison() {
+        Comparison::new("Rating >> 4").unwrap();
+    }
 }
@@ -126,9 +126,8 @@ mod suite {
     where
         T: Display + PartialEq + Eq
     {
-        match result {
-            Ok(result) => panic!("expected error, found '{}'", result),
-            _ => {}
+        if let Ok(result) = result {
+            panic!("expected error, found '{}'", result)
         }
     }
 
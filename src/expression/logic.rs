@@ -49,23 +49,23 @@ where
     }
 
     /// Evaluate an expression recursively
-    pub fn evaluate<'c, 'i>(&self, ast: &Ast, context: &RefCell<CTX>, input: &'i IN) -> Res<OUT> {
+    pub fn evaluate<'i>(&self, ast: &Ast, context: &RefCell<CTX>, input: &'i IN) -> Res<OUT> {
         match ast {
             Ast::Operation(operator) => {
                 match operator {
                     Operator::And(lhs, rhs) => {
                         Ok((self.and)(context,
-                            self.evaluate(&lhs, context, input)?,
-                            self.evaluate(&rhs, context, input)?)?)
+                            self.evaluate(lhs, context, input)?,
+                            self.evaluate(rhs, context, input)?)?)
                     }
                     Operator::Or(lhs, rhs) => {
                         Ok((self.or)(context,
-                            self.evaluate(&lhs, context, input)?,
-                            self.evaluate(&rhs, context, input)?)?)
+                            self.evaluate(lhs, context, input)?,
+                            self.evaluate(rhs, context, input)?)?)
                     }
                     Operator::Not(val) => {
                         Ok((self.not)(context,
-                            self.evaluate(&val, context, input)?)?)
+                            self.evaluate(val, context, input)?)?)
                     }
                 }
             }
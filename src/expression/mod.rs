@@ -11,7 +11,7 @@ pub mod import {
 }
 
 pub mod export {
-    pub use super::comparison::{Comparables, Comparison, Parameters};
+    pub use super::comparison::{Comparables, Comparison, Parameters, Value};
     pub use super::tokenizer::{Tokenizer, Token, ValueToken, BinaryToken, UnaryToken, CloseToken};
     pub use super::parser::{Parser, Operator, Ast};
     pub use super::expression::{Expression};
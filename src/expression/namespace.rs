@@ -5,6 +5,16 @@ pub mod constants {
     pub const RESERVED_TDB: &'static str = "tdb";
     pub const RESERVED_PATH: &'static str = "path";
     pub const RESERVED_KIND: &'static str = "kind";
+    /// Matches the stats of a file, like stat::size::1%
+    pub const RESERVED_STAT: &'static str = "stat";
+    pub const STAT_SIZE: &'static str = "size";
+    pub const STAT_MTIME: &'static str = "mtime";
+    pub const STAT_CTIME: &'static str = "ctime";
+    pub const STAT_UID: &'static str = "uid";
+    pub const STAT_GID: &'static str = "gid";
+    pub const STAT_MODE: &'static str = "mode";
+    /// The stats below the stat namespace
+    pub const STATS: [&'static str; 6] = [STAT_SIZE, STAT_MTIME, STAT_CTIME, STAT_UID, STAT_GID, STAT_MODE];
     pub const NAMESPACE_SEP: &'static str = "::";
 }
 use constants::*;
@@ -19,6 +29,7 @@ lazy_static! {
         set.insert(RESERVED_KIND);
         set.insert(RESERVED_TAG);
         set.insert(RESERVED_TDB);
+        set.insert(RESERVED_STAT);
         set
     };
 
@@ -86,13 +97,25 @@ impl<'n> Namespec<'n> {
     pub fn to_tagspace(&self) -> Namespec<'n> {
         let mut specs = Vec::new();
         specs.push(Namespace::Root);
-        specs.extend(self.slices[2..].iter().map(|e| *e));
+        specs.extend(self.slices[2..].iter().copied());
         Self {
             borrow: self.borrow,
             slices: specs,
         }
     }
 
+    /// Split a canonical spec below the stat namespace into
+    /// the name of the stat and the spec of its value
+    pub fn to_statspace(&self) -> (&'n str, Namespec<'n>) {
+        let name = match self.slices.get(2) {
+            Some(Namespace::User(name)) => *name,
+            _ => "",
+        };
+        let mut specs = vec![Namespace::Root];
+        specs.extend(self.slices.iter().skip(3).copied());
+        (name, Self { borrow: self.borrow, slices: specs })
+    }
+
     pub fn canonicalize_with_default(&self, default: &'n str) -> Self {
         let mut specs = Vec::new();
         match self.slices[0] {
@@ -118,24 +141,22 @@ impl<'n> Namespec<'n> {
 
         let mut specs = Vec::new();
         let slices: Vec<&str> = name.split(NAMESPACE_SEP).collect();
-        match slices.get(0) {
+        match slices.first() {
             Some(&EMPTY) => {
                 specs.push(Namespace::Root);
             }
             Some(&s) => {
                 if RESERVED.contains(s) {
                     specs.push(Namespace::Reserved(s));
-                } else {
-                    if s != EMPTY {
-                        specs.push(Namespace::User(s));
-                    }
+                } else if s != EMPTY {
+                    specs.push(Namespace::User(s));
                 }
             }
             None => return None,
         }
-        for i in 1..slices.len() {
-            if slices[i] != EMPTY {
-                specs.push(Namespace::User(slices[i]));
+        for s in &slices[1..] {
+            if *s != EMPTY {
+                specs.push(Namespace::User(s));
             }
         }
         Some(specs)
@@ -147,7 +168,7 @@ impl<'n> Namespec<'n> {
             let mut i = 0usize;
             let mut j = len;
             let head = {
-                if exp.chars().nth(0) == Some(':')
+                if exp.starts_with(':')
                 && exp.chars().nth(1) != Some(':') {
                     i = 1usize;
                     SHORTHAND_HEAD.as_str()
@@ -179,3 +200,21 @@ impl<'n> From<&'n Namespace<'n>> for &'n str {
         namespace.as_str()
     }
 }
+
+#[cfg(test)]
+mod suite {
+
+    use super::*;
+
+    #[test]
+    fn check_stat() {
+        let (canonical, user) = Namespec::canonicalize_user_expression("size::XL");
+        assert_eq!(canonical.get_reserved().as_str(), RESERVED_TAG);
+        assert_eq!(user.to_string(), "::size::XL");
+        let (canonical, _) = Namespec::canonicalize_user_expression("stat::mtime::2019-12-%");
+        assert_eq!(canonical.get_reserved().as_str(), RESERVED_STAT);
+        let (name, value) = canonical.to_statspace();
+        assert_eq!(name, STAT_MTIME);
+        assert_eq!(value.to_string(), "::2019-12-%");
+    }
+}
@@ -77,23 +77,18 @@ mod suite {
 
     #[test]
     fn check_and() {
-        let e = Parser::new(Tokenizer::new("=[...] & =[...]".chars())).parse().unwrap().unwrap();
-        if let Ast::Operation(node) = e { if let Operator::And(_, _) = node { return } }
-        //panic!()
+        Parser::new(Tokenizer::new("=[...] & =[...]".chars())).parse().unwrap().unwrap();
     }
 
     #[test]
     fn check_or() {
-        let e = Parser::new(Tokenizer::new("=[...] | =[...]".chars())).parse().unwrap().unwrap();
-        if let Ast::Operation(node) = e { if let Operator::Or(_, _) = node { return } }
-        //panic!()
+        Parser::new(Tokenizer::new("=[...] | =[...]".chars())).parse().unwrap().unwrap();
     }
 
     #[test]
     fn check_not() {
         let e = Parser::new(Tokenizer::new("!=[...]".chars())).parse().unwrap().unwrap();
-        if let Ast::Operation(node) = e { if let Operator::Not(_) = node { return } }
-        panic!()
+        assert!(matches!(e, Ast::Operation(Operator::Not(_))));
     }
 
     #[test]
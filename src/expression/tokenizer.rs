@@ -208,9 +208,9 @@ pub mod benches {
     fn bench_1000_ands(b: &mut Bencher) {
         let s = test::black_box(generate_expression(1000, '&'));
         b.iter(|| {
-            let mut tokens = Tokenizer::new(s.chars());
+            let tokens = Tokenizer::new(s.chars());
             let mut n = 0;
-            while let Some(_) = tokens.next() { n += 1; }
+            for _ in tokens { n += 1; }
             n
         });
     }
@@ -219,9 +219,9 @@ pub mod benches {
     fn bench_1000_ors(b: &mut Bencher) {
         let s = test::black_box(generate_expression(1000, '|'));
         b.iter(|| {
-            let mut tokens = Tokenizer::new(s.chars());
+            let tokens = Tokenizer::new(s.chars());
             let mut n = 0;
-            while let Some(_) = tokens.next() { n += 1; }
+            for _ in tokens { n += 1; }
             n
         });
     }
@@ -1,18 +1,26 @@
-#![feature(test, const_fn, type_alias_enum_variants, box_syntax, rustc_private)]
+#![feature(test, box_syntax, rustc_private)]
+#![allow(clippy::redundant_static_lifetimes, clippy::redundant_field_names, clippy::needless_lifetimes, clippy::len_zero)]
+#![allow(clippy::new_without_default, clippy::len_without_is_empty, clippy::ptr_arg, clippy::type_complexity, clippy::module_inception)]
+#![allow(clippy::bool_assert_comparison, clippy::self_named_constructors, clippy::inherent_to_string)]
 #![allow(stable_features)]
 #[macro_use] extern crate diesel;
+#[macro_use] extern crate diesel_migrations;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate lazy_static;
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde;
 extern crate clap;
+extern crate ignore;
+extern crate inotify;
 extern crate internship;
 extern crate md5;
 extern crate owning_ref;
+extern crate rayon;
 extern crate serde_yaml;
 extern crate serde_json;
 extern crate shell_escape;
 extern crate test;
+#[cfg(test)] extern crate tempfile;
 extern crate walkdir;
 extern crate xattr;
 
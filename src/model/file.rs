@@ -1,10 +1,15 @@
-use super::{import::*};
+use super::{import::*, root::{Roots, absolute_path}};
+use crate::expression::namespace::constants::*;
 use diesel::{self, prelude::*, query_builder::{BoxedSelectStatement}};
-use diesel::{expression::{BoxableExpression}, sqlite::Sqlite, sql_types::{Bool, BigInt}};
+use diesel::{expression::{BoxableExpression}, sqlite::Sqlite, sql_types::{Bool, BigInt, Text}};
 
-pub const IDS:
-    (files::id, files::path, files::kind) =
-    (files::id, files::path, files::kind);
+/// The id, absolute path and kind of a file
+pub type IdColumns = (files::id, SqlLiteral<Text>, files::kind);
+
+/// Select the id, absolute path and kind of a file
+pub fn ids() -> IdColumns {
+    (files::id, absolute_path(), files::kind)
+}
 
 /// A 'SELECT' statement this table, returning BigInts
 pub type Select<'e> = BoxedSelectStatement<'e, BigInt, files::table, Sqlite>;
@@ -17,13 +22,140 @@ pub struct File {
     pub id: Fid,
     pub kind: Kind,
     pub path: String,
+    pub device: Option<i64>,
+    pub inode: Option<i64>,
+    pub fingerprint: Option<String>,
+    pub ctime: Option<i64>,
+    pub mtime: Option<i64>,
+    pub size: Option<i64>,
+    pub offline: bool,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    pub mode: Option<i64>,
 }
 
-#[derive(Debug, Insertable)]
-#[table_name="files"]
+/// The columns of a File, with its absolute path
+pub type FileColumns = (files::id, files::kind, SqlLiteral<Text>, files::device, files::inode,
+    files::fingerprint, files::ctime, files::mtime, files::size, files::offline,
+    files::uid, files::gid, files::mode);
+
+/// Select a File
+pub fn columns() -> FileColumns {
+    (files::id, files::kind, absolute_path(), files::device, files::inode,
+        files::fingerprint, files::ctime, files::mtime, files::size, files::offline,
+        files::uid, files::gid, files::mode)
+}
+
+/// The id, size, times, owner and mode of a file
+pub type MetaColumns = (files::id, files::size, files::mtime, files::ctime, files::uid, files::gid, files::mode);
+
+/// Select the size, times, owner and mode of a file
+pub fn meta() -> MetaColumns {
+    (files::id, files::size, files::mtime, files::ctime, files::uid, files::gid, files::mode)
+}
+
+/// Select one of size, times, owner and mode of a file as
+/// text, the way the stat namespace matches it: times as
+/// local dates and the mode in octal
+pub fn meta_text(stat: &str) -> Option<SqlLiteral<Text>> {
+    let column = match stat {
+        STAT_SIZE => "CAST(files.size AS TEXT)",
+        STAT_MTIME => "strftime('%Y-%m-%d %H:%M:%S', files.mtime / 1000000000, 'unixepoch', 'localtime')",
+        STAT_CTIME => "strftime('%Y-%m-%d %H:%M:%S', files.ctime / 1000000000, 'unixepoch', 'localtime')",
+        STAT_UID => "CAST(files.uid AS TEXT)",
+        STAT_GID => "CAST(files.gid AS TEXT)",
+        STAT_MODE => "printf('%o', files.mode)",
+        _ => return None,
+    };
+    Some(sql::<Text>(column))
+}
+
+/// A file to insert, by absolute path
+#[derive(Debug)]
 pub struct Insert<'a> {
     pub kind: i64,
     pub path: &'a str,
+    pub device: Option<i64>,
+    pub inode: Option<i64>,
+    pub fingerprint: Option<String>,
+    pub ctime: Option<i64>,
+    pub mtime: Option<i64>,
+    pub size: Option<i64>,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    pub mode: Option<i64>,
+}
+
+/// The row of a file as stored, by root and relative path
+#[derive(Debug, Insertable)]
+#[table_name="files"]
+struct Row<'a> {
+    kind: i64,
+    root_id: Option<Rid>,
+    path: &'a str,
+    device: Option<i64>,
+    inode: Option<i64>,
+    fingerprint: Option<&'a str>,
+    ctime: Option<i64>,
+    mtime: Option<i64>,
+    size: Option<i64>,
+    uid: Option<i64>,
+    gid: Option<i64>,
+    mode: Option<i64>,
+}
+
+impl<'a> Insert<'a> {
+    /// The row to store, relative to its root
+    fn to_row<'s>(&'s self, roots: &Roots) -> Row<'s> {
+        let (root, path) = roots.split(self.path);
+        Row {
+            kind: self.kind,
+            root_id: root,
+            path: path,
+            device: self.device,
+            inode: self.inode,
+            fingerprint: self.fingerprint.as_deref(),
+            ctime: self.ctime,
+            mtime: self.mtime,
+            size: self.size,
+            uid: self.uid,
+            gid: self.gid,
+            mode: self.mode,
+        }
+    }
+}
+
+/// The columns we compare to detect changed files, those
+/// telling whether a vanished file is offline, and the mode
+pub type StatColumns = (files::id, SqlLiteral<Text>, files::ctime, files::mtime, files::size,
+    files::device, files::offline, files::mode);
+
+/// Select the stats of a file
+pub fn stats() -> StatColumns {
+    (files::id, absolute_path(), files::ctime, files::mtime, files::size,
+        files::device, files::offline, files::mode)
+}
+
+/// A file's id, path, stats, device, whether it is
+/// offline and its mode, as stored in the database
+#[derive(Debug, Queryable, PartialEq, Eq, Clone)]
+pub struct Stats {
+    pub id: Fid,
+    pub path: String,
+    pub ctime: Option<i64>,
+    pub mtime: Option<i64>,
+    pub size: Option<i64>,
+    pub device: Option<i64>,
+    pub offline: bool,
+    pub mode: Option<i64>,
+}
+
+/// A file that has been moved to a new path
+#[derive(Debug)]
+pub struct Move<'a> {
+    pub id: Fid,
+    pub from: String,
+    pub to: Insert<'a>,
 }
 
 pub trait FileExt {
@@ -74,11 +206,14 @@ impl File {
 
     /// Insert files into the database
     pub fn insert_all(values: &Vec<Insert>, c: &db::Connection) -> Res<Vec<Self>> {
+        let roots = Roots::query(c)?;
+        let rows: Vec<Row> = values.iter().map(|v| v.to_row(&roots)).collect();
         c.get().transaction::<_, Error, _>(|| {
-            diesel::insert_into(files::table).values(values).execute(c.get())?;
-            Ok(files::table.filter(
-                files::path.eq_any(values.iter().map(|e| e.path))
-            ).get_results(c.get())?)
+            diesel::insert_into(files::table).values(&rows).execute(c.get())?;
+            Ok(files::table
+                .select(columns())
+                .filter(roots.at_any(values.iter().map(|e| e.path)))
+                .get_results(c.get())?)
         })
     }
 
@@ -89,7 +224,114 @@ impl File {
 
     /// Delete files from the database, by path
     pub fn delete_paths(paths: &Vec<&str>, c: &db::Connection) -> Res<usize> {
-        Ok(diesel::delete(files::table.filter(files::path.eq_any(paths))).execute(c.get())?)
+        let roots = Roots::query(c)?;
+        Ok(diesel::delete(files::table.filter(roots.at_any(paths.iter().cloned()))).execute(c.get())?)
+    }
+
+    /// Count the files at or below the given path
+    pub fn count_below(path: &str, c: &db::Connection) -> Res<i64> {
+        let roots = Roots::query(c)?;
+        Ok(files::table.filter(roots.below(path)).count().get_result(c.get())?)
+    }
+
+    /// Query the stats of the files at or below the given
+    /// path, ordered by id and starting after one
+    pub fn query_below(path: &str, after: Fid, limit: usize, c: &db::Connection) -> Res<Vec<Stats>> {
+        let roots = Roots::query(c)?;
+        Ok(files::table
+            .select(stats())
+            .filter(roots.below(path))
+            .filter(files::id.gt(after))
+            .order(files::id)
+            .limit(limit as i64)
+            .get_results(c.get())?)
+    }
+
+    /// Mark files as offline, or as online again
+    pub fn set_offline(ids: &Vec<Fid>, offline: bool, c: &db::Connection) -> Res<usize> {
+        Ok(diesel::update(files::table.filter(files::id.eq_any(ids)))
+            .set(files::offline.eq(offline))
+            .execute(c.get())?)
+    }
+
+    /// Query the paths of all files which are neither one
+    /// of the given roots nor below one
+    pub fn query_outside(roots: &Vec<&str>, c: &db::Connection) -> Res<Vec<String>> {
+        let known = Roots::query(c)?;
+        let mut query = files::table.select(absolute_path()).order(absolute_path()).into_boxed();
+        for root in roots.iter() {
+            query = query.filter(not(known.below(root)));
+        }
+        Ok(query.get_results(c.get())?)
+    }
+
+    /// Query files from the database, by path
+    pub fn query_paths(paths: &Vec<&str>, c: &db::Connection) -> Res<Vec<Self>> {
+        let roots = Roots::query(c)?;
+        Ok(files::table.select(columns()).filter(roots.at_any(paths.iter().cloned())).get_results(c.get())?)
+    }
+
+    /// Query files without a known device and inode, by path
+    pub fn query_unidentified(paths: &Vec<&str>, c: &db::Connection) -> Res<Vec<Self>> {
+        let roots = Roots::query(c)?;
+        Ok(files::table
+            .select(columns())
+            .filter(roots.at_any(paths.iter().cloned()))
+            .filter(files::device.is_null().or(files::inode.is_null()))
+            .get_results(c.get())?)
+    }
+
+    /// Move files to their new paths, keeping their ids
+    pub fn move_all(moves: &Vec<Move>, c: &db::Connection) -> Res<usize> {
+        let roots = Roots::query(c)?;
+        c.get().transaction::<_, Error, _>(|| {
+            let mut n = 0;
+            for m in moves.iter() {
+                let (root, path) = roots.split(m.to.path);
+                n += diesel::update(files::table.find(m.id))
+                    .set((
+                        files::root_id.eq(root),
+                        files::path.eq(path),
+                        files::kind.eq(m.to.kind),
+                        files::device.eq(m.to.device),
+                        files::inode.eq(m.to.inode),
+                        files::fingerprint.eq(&m.to.fingerprint),
+                        files::ctime.eq(m.to.ctime),
+                        files::mtime.eq(m.to.mtime),
+                        files::size.eq(m.to.size),
+                        files::uid.eq(m.to.uid),
+                        files::gid.eq(m.to.gid),
+                        files::mode.eq(m.to.mode),
+                    ))
+                    .execute(c.get())?;
+            }
+            Ok(n)
+        })
+    }
+
+    /// Record the times, size, owner and mode of a file
+    pub fn update_stats(id: Fid, stat: &util::file::Stat, c: &db::Connection) -> Res<usize> {
+        Ok(diesel::update(files::table.find(id))
+            .set((
+                files::ctime.eq(stat.ctime),
+                files::mtime.eq(stat.mtime),
+                files::size.eq(stat.size),
+                files::uid.eq(stat.uid),
+                files::gid.eq(stat.gid),
+                files::mode.eq(stat.mode),
+            ))
+            .execute(c.get())?)
+    }
+
+    /// Record the device, inode and fingerprint of a file
+    pub fn update_identity(id: Fid, identity: &Insert, c: &db::Connection) -> Res<usize> {
+        Ok(diesel::update(files::table.find(id))
+            .set((
+                files::device.eq(identity.device),
+                files::inode.eq(identity.inode),
+                files::fingerprint.eq(&identity.fingerprint),
+            ))
+            .execute(c.get())?)
     }
 }
 
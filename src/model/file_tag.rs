@@ -9,7 +9,7 @@ pub const IDS:
 
 // These are some helper types to wrangle the mess
 // of diesels type gibberish.
-pub type SqlType = <(file_tags::file_id) as Expression>::SqlType;
+pub type SqlType = <file_tags::file_id as Expression>::SqlType;
 pub type FilesJoined = JoinOn<Join<file_tags::table, files::table, Inner>, Eq<Nullable<file_tags::file_id>, Nullable<files::id>>>;
 pub type TagsJoined = JoinOn<Join<file_tags::table, tags::table, Inner>, Eq<Nullable<file_tags::tag_id>, Nullable<tags::id>>>;
 pub type FileTagsJoined = JoinOn<Join<FilesJoined, tags::table, Inner>, Eq<Nullable<file_tags::tag_id>, Nullable<tags::id>>>;
@@ -1,6 +1,8 @@
 pub mod file;
 pub mod tag;
 pub mod file_tag;
+pub mod progress;
+pub mod root;
 
 pub mod prelude {
     pub type Uid = i64;
@@ -8,10 +10,12 @@ pub mod prelude {
     pub type Fid = Uid;
     pub type Bid = Uid;
     pub type Tid = Uid;
+    pub type Rid = Uid;
     pub type Ids = (Fid, Tid);
     pub type Row = (Fid, String, Tid, String);
     pub type TCol = (Uid, String);
     pub type FCol = (Uid, String, Kind);
+    pub type MCol = (Uid, Option<i64>, Option<i64>, Option<i64>, Option<i64>, Option<i64>, Option<i64>);
 }
 
 pub mod import {
@@ -34,5 +38,7 @@ pub mod export {
     pub use super::file::*;
     pub use super::tag::*;
     pub use super::file_tag::*;
+    pub use super::progress::*;
+    pub use super::root::*;
 }
 pub use export::*;
@@ -0,0 +1,28 @@
+use super::import::*;
+
+/// The last path a batched update committed below
+/// a root, so an interrupted update can resume
+#[derive(Debug, Queryable, Insertable, PartialEq, Eq, Clone)]
+#[table_name="update_progress"]
+pub struct Progress {
+    pub root: String,
+    pub path: String,
+}
+
+impl Progress {
+
+    /// Query the progress of an update below root
+    pub fn query(root: &str, c: &db::Connection) -> Res<Option<Self>> {
+        Ok(update_progress::table.find(root).get_result(c.get()).optional()?)
+    }
+
+    /// Record the progress of an update
+    pub fn save(&self, c: &db::Connection) -> Res<usize> {
+        Ok(diesel::replace_into(update_progress::table).values(self).execute(c.get())?)
+    }
+
+    /// Forget the progress of a finished update
+    pub fn clear(root: &str, c: &db::Connection) -> Res<usize> {
+        Ok(diesel::delete(update_progress::table.find(root)).execute(c.get())?)
+    }
+}
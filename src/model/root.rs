@@ -0,0 +1,251 @@
+use super::{import::*, file::Boolean};
+use diesel::sql_types::Text;
+use crate::util::sql::{sql_false, escape_like};
+
+/// The absolute path of a file, from its root and the
+/// path relative to it
+const ABSOLUTE_PATH: &'static str = "(CASE \
+    WHEN files.root_id IS NULL THEN files.path \
+    WHEN files.path = '' THEN (SELECT roots.path FROM roots WHERE roots.id = files.root_id) \
+    ELSE (SELECT rtrim(roots.path, '/') FROM roots WHERE roots.id = files.root_id) || '/' || files.path \
+END)";
+
+/// Select the absolute path of a file
+pub fn absolute_path() -> SqlLiteral<Text> {
+    sql::<Text>(ABSOLUTE_PATH)
+}
+
+/// The prefix of the paths below a directory
+fn prefix(path: &str) -> String {
+    format!("{}/", path.trim_end_matches('/'))
+}
+
+/// A LIKE pattern matching the paths below a directory
+fn below_pattern(path: &str) -> String {
+    format!("{}%", escape_like(&prefix(path)))
+}
+
+/// A directory the paths of files below it are stored
+/// relative to, so it can be moved without a rescan
+#[derive(Debug, Queryable, PartialEq, Eq, Clone)]
+pub struct Root {
+    pub id: Rid,
+    pub path: String,
+}
+
+/// All roots, translating absolute paths to the stored
+/// ones. Deeper roots come first.
+#[derive(Debug, Clone)]
+pub struct Roots(Vec<Root>);
+
+impl Roots {
+
+    /// Query all roots
+    pub fn query(c: &db::Connection) -> Res<Self> {
+        let mut roots: Vec<Root> = roots::table.get_results(c.get())?;
+        roots.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        Ok(Roots(roots))
+    }
+
+    /// Split an absolute path into the closest root and
+    /// the path relative to it
+    pub fn split<'p>(&self, path: &'p str) -> (Option<Rid>, &'p str) {
+        for root in self.0.iter() {
+            if let Some(relative) = Path::new(path).strip_prefix(&root.path).ok().and_then(|p| p.to_str()) {
+                return (Some(root.id), relative)
+            }
+        }
+        (None, path)
+    }
+
+    /// Matches the files of a root, or those without one
+    fn of(root: Option<Rid>) -> Boolean<'static> {
+        match root {
+            Some(id) => box files::root_id.eq(id),
+            None => box files::root_id.is_null(),
+        }
+    }
+
+    /// Matches the files at any of the given paths
+    pub fn at_any<'p>(&self, paths: impl IntoIterator<Item=&'p str>) -> Boolean<'static> {
+        let mut by_root: HashMap<Option<Rid>, Vec<String>> = HashMap::new();
+        for path in paths {
+            let (root, path) = self.split(path);
+            by_root.entry(root).or_insert_with(Vec::new).push(path.to_string());
+        }
+        let mut matches: Boolean<'static> = box sql_false();
+        for (root, paths) in by_root {
+            matches = box matches.or(Self::of(root).and(files::path.eq_any(paths)));
+        }
+        matches
+    }
+
+    /// Matches the files at or below the given path,
+    /// including those of roots below it
+    pub fn below(&self, path: &str) -> Boolean<'static> {
+        let mut matches: Boolean<'static> = match self.split(path) {
+            (Some(id), "") => Self::of(Some(id)),
+            (root, relative) => {
+                let below = below_pattern(relative);
+                box Self::of(root).and(files::path.eq(relative.to_string()).or(files::path.like(below).escape('\\')))
+            },
+        };
+        let nested: Vec<Rid> = self.0.iter()
+            .filter(|r| r.path != path && Path::new(&r.path).starts_with(path))
+            .map(|r| r.id)
+            .collect();
+        if nested.len() > 0 { matches = box matches.or(files::root_id.eq_any(nested)); }
+        matches
+    }
+
+    /// Add a root, if it is new. Files already indexed
+    /// below it are stored relative to it from now on.
+    pub fn register(path: &str, c: &db::Connection) -> Res<Rid> {
+        let roots = Self::query(c)?;
+        if let Some(root) = roots.0.iter().find(|r| r.path == path) { return Ok(root.id) }
+        c.get().transaction::<_, Error, _>(|| {
+            diesel::insert_into(roots::table).values(roots::path.eq(path)).execute(c.get())?;
+            let id: Rid = roots::table.select(roots::id).filter(roots::path.eq(path)).get_result(c.get())?;
+            // Files of roots above this one move over,
+            // those of deeper roots stay where they are.
+            let above: Vec<String> = roots.0.iter()
+                .filter(|r| Path::new(path).starts_with(&r.path))
+                .map(|r| r.id.to_string())
+                .collect();
+            let owners = match above.len() {
+                0 => "files.root_id IS NULL".to_string(),
+                _ => format!("(files.root_id IS NULL OR files.root_id IN ({}))", above.join(", ")),
+            };
+            let adopted = diesel::sql_query(format!(
+                "UPDATE files SET \
+                    path = CASE WHEN {0} = ?1 THEN '' ELSE substr({0}, length(?3) + 1) END, \
+                    root_id = ?2 \
+                WHERE {1} AND ({0} = ?1 OR {0} LIKE ?4 ESCAPE '\\')", ABSOLUTE_PATH, owners))
+                .bind::<Text, _>(path)
+                .bind::<BigInt, _>(id)
+                .bind::<Text, _>(prefix(path))
+                .bind::<Text, _>(below_pattern(path))
+                .execute(c.get())?;
+            info!("ROOT: {} ({} File(s))", path, adopted);
+            Ok(id)
+        })
+    }
+
+    /// Add all given roots which are new
+    pub fn register_all(paths: &Vec<PathBuf>, c: &db::Connection) -> Res<()> {
+        for path in paths.iter() { Self::register(&path.to_string_lossy(), c)?; }
+        Ok(())
+    }
+
+    /// Move a root and the roots below it to a new path,
+    /// returning how many were moved
+    pub fn relocate(from: &str, to: &str, c: &db::Connection) -> Res<usize> {
+        c.get().transaction::<_, Error, _>(|| {
+            Self::register(from, c)?;
+            Ok(diesel::sql_query(
+                "UPDATE roots SET \
+                    path = CASE WHEN path = ?1 THEN ?2 ELSE ?4 || substr(path, length(?3) + 1) END \
+                WHERE path = ?1 OR path LIKE ?5 ESCAPE '\\'")
+                .bind::<Text, _>(from)
+                .bind::<Text, _>(to)
+                .bind::<Text, _>(prefix(from))
+                .bind::<Text, _>(prefix(to))
+                .bind::<Text, _>(below_pattern(from))
+                .execute(c.get())?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod suite {
+    use super::*;
+    use super::super::file::{self, File};
+    use crate::util::testing;
+
+    /// Index files at the given absolute paths
+    fn insert(paths: &[&str], c: &db::Connection) {
+        let values = paths.iter().map(|p| file::Insert {
+            kind: 0, path: p, device: None, inode: None, fingerprint: None,
+            ctime: None, mtime: None, size: None, uid: None, gid: None, mode: None,
+        }).collect();
+        File::insert_all(&values, c).unwrap();
+    }
+
+    /// The stored root and path of all files, and their
+    /// absolute path
+    fn stored(c: &db::Connection) -> Vec<(Option<String>, String, String)> {
+        let roots = Roots::query(c).unwrap();
+        let rows: Vec<(Option<Rid>, String, String)> = files::table
+            .select((files::root_id, files::path, absolute_path()))
+            .order(files::id)
+            .get_results(c.get()).unwrap();
+        rows.into_iter()
+            .map(|(id, path, absolute)| (id.map(|id| roots.0.iter().find(|r| r.id == id).unwrap().path.clone()), path, absolute))
+            .collect()
+    }
+
+    fn row(root: Option<&str>, path: &str, absolute: &str) -> (Option<String>, String, String) {
+        (root.map(String::from), path.into(), absolute.into())
+    }
+
+    #[test]
+    fn check_split() {
+        let roots = Roots(vec![Root { id: 2, path: "/music".into() }, Root { id: 1, path: "/".into() }]);
+        assert_eq!(roots.split("/music"), (Some(2), ""));
+        assert_eq!(roots.split("/music/a/b"), (Some(2), "a/b"));
+        assert_eq!(roots.split("/musicals/a"), (Some(1), "musicals/a"));
+        assert_eq!(roots.split("/a"), (Some(1), "a"));
+        assert_eq!(roots.split("/"), (Some(1), ""));
+        let roots = Roots(vec![Root { id: 2, path: "/music".into() }]);
+        assert_eq!(roots.split("/musicals/a"), (None, "/musicals/a"));
+    }
+
+    #[test]
+    fn check_below() {
+        let (_dir, path) = testing::temp_dir();
+        let c = testing::database(&path);
+        Roots::register("/lib", &c).unwrap();
+        insert(&["/lib/a_b/x", "/lib/aXb/y", "/lib/a_bc/z", "/other/a%b/x", "/other/aXXb/y"], &c);
+        assert_eq!(File::count_below("/lib", &c).unwrap(), 3);
+        assert_eq!(File::count_below("/lib/a_b", &c).unwrap(), 1);
+        assert_eq!(File::count_below("/lib/a_b/x", &c).unwrap(), 1);
+        assert_eq!(File::count_below("/other/a%b", &c).unwrap(), 1);
+        assert_eq!(File::count_below("/", &c).unwrap(), 5);
+    }
+
+    #[test]
+    fn check_register() {
+        let (_dir, path) = testing::temp_dir();
+        let c = testing::database(&path);
+        insert(&["/lib/a", "/lib/sub/b", "/lib_x/c"], &c);
+        Roots::register("/lib", &c).unwrap();
+        assert_eq!(stored(&c), vec![
+            row(Some("/lib"), "a", "/lib/a"),
+            row(Some("/lib"), "sub/b", "/lib/sub/b"),
+            row(None, "/lib_x/c", "/lib_x/c"),
+        ]);
+        Roots::register("/lib/sub", &c).unwrap();
+        Roots::register("/", &c).unwrap();
+        assert_eq!(stored(&c), vec![
+            row(Some("/lib"), "a", "/lib/a"),
+            row(Some("/lib/sub"), "b", "/lib/sub/b"),
+            row(Some("/"), "lib_x/c", "/lib_x/c"),
+        ]);
+        insert(&["/lib/sub"], &c);
+        assert_eq!(stored(&c).pop(), Some(row(Some("/lib/sub"), "", "/lib/sub")));
+    }
+
+    #[test]
+    fn check_relocate() {
+        let (_dir, path) = testing::temp_dir();
+        let c = testing::database(&path);
+        insert(&["/a_b/x", "/a_b/sub/y", "/aXb/z"], &c);
+        for root in &["/a_b", "/a_b/sub", "/aXb"] { Roots::register(root, &c).unwrap(); }
+        assert_eq!(Roots::relocate("/a_b", "/mnt/a_b", &c).unwrap(), 2);
+        assert_eq!(stored(&c), vec![
+            row(Some("/mnt/a_b"), "x", "/mnt/a_b/x"),
+            row(Some("/mnt/a_b/sub"), "y", "/mnt/a_b/sub/y"),
+            row(Some("/aXb"), "z", "/aXb/z"),
+        ]);
+    }
+}
@@ -9,12 +9,14 @@ pub const IDS:
 pub struct Tag {
     pub id: i64,
     pub name: String,
+    pub value: Option<String>,
 }
 
 #[derive(Debug, Insertable)]
 #[table_name="tags"]
 pub struct Insert<'a> {
     pub name: &'a str,
+    pub value: Option<&'a str>,
 }
 
 pub trait TagExt {
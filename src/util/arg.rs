@@ -22,10 +22,7 @@ impl<'o, 'a> Options<'o, 'a> {
     }
 
     pub fn _opt_vec<'f>(args: &'f ArgMatches, key: &str) -> Option<Vec<&'f str>> {
-        match args.values_of(key) {
-            Some(v) => Some(v.collect()),
-            None => None,
-        }
+        args.values_of(key).map(|v| v.collect())
     }
 
     pub fn _opt<'f>(args: &'f ArgMatches, key: &str) -> Option<&'f str> {
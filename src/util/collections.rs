@@ -1,5 +1,5 @@
 use super::import::*;
-use std::collections::{hash_map::RandomState, hash_set::Difference};
+use std::collections::{hash_map::{Entry, RandomState}, hash_set::Difference};
 use std::hash::Hash;
 
 pub mod error {
@@ -43,11 +43,11 @@ where
     }
     /// Map an int to another int
     pub fn map(&mut self, l: L, r: R) -> (bool, bool) {
-        let lonce = if !self.ltor.contains_key(&l) {
-            self.ltor.insert(l, HashSet::with_capacity(2)); true
+        let lonce = if let Entry::Vacant(e) = self.ltor.entry(l) {
+            e.insert(HashSet::with_capacity(2)); true
         } else { false };
-        let ronce = if !self.rtol.contains_key(&r) {
-            self.rtol.insert(r, HashSet::with_capacity(2)); true
+        let ronce = if let Entry::Vacant(e) = self.rtol.entry(r) {
+            e.insert(HashSet::with_capacity(2)); true
         } else { false };
         self.ltor.get_mut(&l).unwrap().insert(r);
         self.rtol.get_mut(&r).unwrap().insert(l);
@@ -104,12 +104,12 @@ where
     }
     /// Return the str associated with an int
     pub fn by_uid(&self, id: L) -> Res<R> {
-        self.itos.get(&id).map(|id| *id)
+        self.itos.get(&id).copied()
             .ok_or(E::UnknownId { id: "".into() }.into())
     }
     /// Return the int associated with an str
     pub fn by_alt(&self, id: R) -> Res<L> {
-        self.stoi.get(&id).map(|id| *id)
+        self.stoi.get(&id).copied()
             .ok_or(E::UnknownId { id: "".into() }.into())
     }
     /// Map an int to a str
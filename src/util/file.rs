@@ -28,26 +28,76 @@ impl UnixFileType {
     pub fn to_i64(self) -> i64 {
         self as i64
     }
-    pub fn from_std(ft: &std::fs::FileType) -> UnixFileType {
-        if      ft.is_file()         { Self::File }
-        else if ft.is_dir()          { Self::Dir }
-        else if ft.is_symlink()      { Self::Symlink }
-        else if ft.is_socket()       { Self::Socket }
-        else if ft.is_fifo()         { Self::Fifo }
-        else if ft.is_block_device() { Self::BlockDevice }
-        else if ft.is_char_device()  { Self::CharDevice }
-        // TODO: improve error handling
-        else                         { panic!("bug: unsupported file type") }
+    pub fn from_std(ft: &std::fs::FileType) -> Option<UnixFileType> {
+        if      ft.is_file()         { Some(Self::File) }
+        else if ft.is_dir()          { Some(Self::Dir) }
+        else if ft.is_symlink()      { Some(Self::Symlink) }
+        else if ft.is_socket()       { Some(Self::Socket) }
+        else if ft.is_fifo()         { Some(Self::Fifo) }
+        else if ft.is_block_device() { Some(Self::BlockDevice) }
+        else if ft.is_char_device()  { Some(Self::CharDevice) }
+        else                         { None }
     }
 }
 
+/// The times, size, owner and permissions of a file, the
+/// first three used for detecting changes. Times are in
+/// nanoseconds since the epoch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Stat {
+    pub ctime: i64,
+    pub mtime: i64,
+    pub size: i64,
+    pub uid: i64,
+    pub gid: i64,
+    pub mode: i64,
+}
+
+/// Return the times, size, owner and permissions of a file
+pub fn stat(path: &str) -> Option<Stat> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| Stat {
+        ctime: m.ctime() * 1_000_000_000 + m.ctime_nsec(),
+        mtime: m.mtime() * 1_000_000_000 + m.mtime_nsec(),
+        size: m.size() as i64,
+        uid: m.uid() as i64,
+        gid: m.gid() as i64,
+        mode: (m.mode() & 0o7777) as i64,
+    })
+}
+
+/// Return the device and inode of a file, which stay
+/// the same when it is moved within a filesystem
+pub fn identity(path: &str) -> Option<(i64, i64)> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| (m.dev() as i64, m.ino() as i64))
+}
+
+/// Return an md5 digest of a regular files contents
+pub fn fingerprint(path: &str) -> Option<String> {
+    use std::io::Read;
+    let mut file = std::fs::File::open(path).ok()?;
+    if !file.metadata().ok()?.is_file() { return None }
+    let mut context = md5::Context::new();
+    let mut buffer = [0u8; 1 << 16];
+    loop {
+        match file.read(&mut buffer).ok()? {
+            0 => break,
+            n => context.consume(&buffer[..n]),
+        }
+    }
+    Some(format!("{:x}", context.compute()))
+}
+
+/// Return the type of a file, if it is one we know. The
+/// file isn't opened, so this doesn't block on fifos.
+pub fn file_type(path: &str) -> std::io::Result<Option<UnixFileType>> {
+    Ok(UnixFileType::from_std(&std::fs::metadata(path)?.file_type()))
+}
+
+/// Return the type of a file. Scans skip files of unknown
+/// type, so we only fall back to File if the file changed
+/// since.
 pub fn get_file_type(path: &str) -> UnixFileType {
-    use std::fs::File;
-    UnixFileType::from_std(
-        &File::open(path)
-            .expect("FIXME: improve error handling")
-            .metadata()
-            .expect("FIXME: improve error handling")
-            .file_type()
-    )
+    file_type(path).ok().and_then(|t| t).unwrap_or(UnixFileType::File)
 }
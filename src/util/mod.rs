@@ -6,6 +6,7 @@ pub mod string;
 pub mod file;
 pub mod collections;
 #[macro_use] pub mod profiler;
+#[cfg(test)] pub mod testing;
 
 pub mod import {
     pub use super::super::import::*;
@@ -93,6 +93,7 @@ impl Timer {
 #[derive(Clone)]
 pub struct AnalyzedFrame {
     index: Handle,
+    #[allow(dead_code)]
     parent: Handle,
     total: i64,
     tally: Vec<(Handle, i64)>
@@ -159,7 +160,7 @@ impl Frame {
         self.parent
     }
     pub fn name(&self) -> &str {
-        &self.name
+        self.name
     }
     pub fn enter(&mut self) -> DateTime<Utc> {
         self.timer.enter()
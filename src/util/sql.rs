@@ -36,10 +36,22 @@ pub fn with_pairs<'a, T: Display>(columns: (&'a str, &'a str), values: &'a Vec<(
 pub fn sql_text(text: &str) -> SqlLiteral<Text>
 { sql::<Text>(&format!("'{}'", text)) }
 
+/// Escape the wildcards of a LIKE pattern, for use
+/// with ESCAPE '\'
+pub fn escape_like(text: &str) -> String
+{
+    let mut s = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || c == '%' || c == '_' { s.push('\\'); }
+        s.push(c);
+    }
+    s
+}
+
 pub fn sql_false() -> SqlLiteral<Bool>
-{ sql::<Bool>(&format!("FALSE")) }
+{ sql::<Bool>("FALSE") }
 
 pub fn sql_true() -> SqlLiteral<Bool>
-{ sql::<Bool>(&format!("TRUE")) }
+{ sql::<Bool>("TRUE") }
 
 // }}}
@@ -0,0 +1,20 @@
+//! Fixtures shared by the test suites
+
+use super::import::*;
+use crate::db::export::*;
+pub use tempfile::TempDir;
+
+/// Create an empty temporary directory. It is removed once
+/// the returned handle is dropped, so keep it around for as
+/// long as its canonical path is used.
+pub fn temp_dir() -> (TempDir, PathBuf) {
+    let dir = tempfile::Builder::new().prefix("tag-suite-").tempdir().unwrap();
+    let path = dir.path().canonicalize().unwrap();
+    (dir, path)
+}
+
+/// Open a new database in the directory
+pub fn database(dir: &Path) -> db::Connection {
+    let pool = db::Connection::new_pool(&dir.join("db.sqlite").to_string_lossy(), 1).unwrap();
+    db::Connection(pool.get().unwrap())
+}
@@ -12,3 +12,4 @@ done
 echo "a" > "${1}/a"
 echo "b" > "${1}/b"
 echo "c" > "${1}/c"
+echo "d" > "${1}/d"